@@ -1,5 +1,6 @@
 pub mod ladder_view;
 pub mod market_list;
+pub mod price_chart;
 
 use leptos::*;
 use leptos_router::Outlet;
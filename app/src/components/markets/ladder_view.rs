@@ -1,18 +1,21 @@
-use std::collections::HashMap;
-
-use futures::{FutureExt, SinkExt, StreamExt};
+use futures::{SinkExt, StreamExt};
 use gloo_net::websocket::futures::WebSocket;
-use gloo_net::websocket::Message;
+use gloo_net::websocket::{Message, WebSocketError};
 use gloo_timers;
 use leptos::ev::SubmitEvent;
 use leptos::html::Input;
 use leptos::*;
 use leptos_router::*;
 use rust_decimal_macros::dec;
-use trading_types::common::{Order, RequestId, Side, Size};
-use trading_types::from_server::{Latency, ServerMessage, TickData, TraderOrders};
+use trading_types::common::{MarketStatus, Order, RequestId, Selection, SelectionId, Side, Size};
+use trading_types::from_server::{
+    Latency, Position, ServerMessage, Settlement, TickData, Trade, TraderInfo, TraderOrders,
+    TRADER_ALREADY_CONNECTED,
+};
 use trading_types::from_trader::TraderMessage;
 
+use super::price_chart::PriceChart;
+
 #[component]
 pub fn LadderView(cx: Scope) -> impl IntoView {
     let params = use_params_map(cx);
@@ -37,6 +40,19 @@ impl PartialEq for SenderWrapper {
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
+struct OrderFeedback {
+    request_id: RequestId,
+    message: String,
+    is_error: bool,
+}
+
+/// How many of the latest order acknowledgements are shown to the trader
+const MAX_ORDER_FEEDBACK: usize = 5;
+
+/// How many of the latest trades are kept in the time and sales panel
+const MAX_TRADES: usize = 100;
+
 #[derive(Debug, Clone, PartialEq)]
 struct TickDataWrapper {
     id: usize,
@@ -44,26 +60,142 @@ struct TickDataWrapper {
     is_last_traded: RwSignal<bool>,
 }
 
+/// Tracks the sequence number of ladder updates to notice missed ones
+#[derive(Debug, Default)]
+struct LadderSequence {
+    /// `None` until a snapshot arrives
+    last: Option<u64>,
+}
+
+enum SequenceCheck {
+    /// The update continues the ladder
+    Apply,
+    /// The update is already part of the ladder, or there is no snapshot to apply it to yet
+    Skip,
+    /// Updates were missed, so the market state is needed again
+    Gap,
+}
+
+impl LadderSequence {
+    fn reset(&mut self, sequence: u64) {
+        self.last = Some(sequence);
+    }
+
+    /// Check a delta covering the updates after `previous` up to `sequence`. A delta that starts
+    /// before the ladder still applies, as it holds the latest state of every tick it has.
+    fn check(&mut self, previous: u64, sequence: u64) -> SequenceCheck {
+        match self.last {
+            Some(last) if sequence <= last => SequenceCheck::Skip,
+            Some(last) if previous <= last => {
+                self.last = Some(sequence);
+                SequenceCheck::Apply
+            }
+            Some(last) => {
+                log!("missed ladder updates {}..={}", last + 1, previous);
+                self.last = None;
+                SequenceCheck::Gap
+            }
+            None => SequenceCheck::Skip,
+        }
+    }
+}
+
+/// Tracks the sequence number of trades to notice missed ones
+#[derive(Debug, Default)]
+struct TradeSequence {
+    /// `None` while the history has no trades yet
+    last: Option<u64>,
+    /// Trades were missed and the history is on its way
+    stale: bool,
+}
+
+impl TradeSequence {
+    /// Continue from the latest trade of the history, oldest first
+    fn reset(&mut self, history: &[Trade]) {
+        self.last = history.last().map(|trade| trade.sequence);
+        self.stale = false;
+    }
+
+    fn check(&mut self, sequence: u64) -> SequenceCheck {
+        match self.last {
+            _ if self.stale => SequenceCheck::Skip,
+            Some(last) if sequence <= last => SequenceCheck::Skip,
+            Some(last) if sequence > last + 1 => {
+                log!("missed trades {}..{}", last + 1, sequence);
+                self.stale = true;
+                SequenceCheck::Gap
+            }
+            _ => {
+                self.last = Some(sequence);
+                SequenceCheck::Apply
+            }
+        }
+    }
+}
+
+fn apply_tick_update(ladder: &mut [TickDataWrapper], new_value: &TickData) {
+    ladder.iter_mut().for_each(|x| {
+        let tick_data = x.tick_data.get_untracked();
+        if tick_data.selection == new_value.selection && tick_data.tick == new_value.tick {
+            x.tick_data.update(|prev| {
+                *prev = new_value.clone();
+            });
+        }
+    });
+}
+
+fn set_latest_match(ladder: &mut [TickDataWrapper], new_value: &TickData) {
+    ladder
+        .iter_mut()
+        .filter(|x| x.tick_data.get_untracked().selection == new_value.selection)
+        .for_each(|x| {
+            let is_last_traded = x.tick_data.get_untracked().tick == new_value.tick;
+            x.is_last_traded.update(|prev| {
+                *prev = is_last_traded;
+            });
+        });
+}
+
 #[component]
 fn LadderViewInternal(cx: Scope, id: Memo<u32>) -> impl IntoView {
-    let derived_ws_url = create_memo::<String>(cx, move |_| derive_ws_url(id()));
+    // Connects again with a fresh trader id when the server refuses the stored one
+    let reconnect = create_trigger(cx);
+    let derived_ws_url = create_memo::<String>(cx, move |_| {
+        reconnect.track();
+        derive_ws_url(id())
+    });
     let (latency, set_latency) = create_signal::<Option<Latency>>(cx, None);
+    let (market_status, set_market_status) = create_signal::<Option<MarketStatus>>(cx, None);
+    let (trader_info, set_trader_info) = create_signal::<Option<TraderInfo>>(cx, None);
+    let (selections, set_selections) = create_signal::<Vec<Selection>>(cx, vec![]);
+    let (selected, set_selected) = create_signal::<Option<SelectionId>>(cx, None);
     let (ladder, set_ladder) = create_signal::<Vec<TickDataWrapper>>(cx, vec![]);
-    let (trader_orders, set_trader_orders) = create_signal::<TraderOrders>(
-        cx,
-        TraderOrders { matched_orders: HashMap::new(), unmatched_orders: HashMap::new() },
-    );
+    let selected_ladder = create_memo::<Vec<TickDataWrapper>>(cx, move |_| {
+        ladder()
+            .into_iter()
+            .filter(|row| Some(row.tick_data.get_untracked().selection) == selected())
+            .collect()
+    });
+    let (trader_orders, set_trader_orders) =
+        create_signal::<TraderOrders>(cx, TraderOrders::default());
+    let (order_feedback, set_order_feedback) = create_signal::<Vec<OrderFeedback>>(cx, vec![]);
+    let (statement, set_statement) = create_signal::<Vec<Settlement>>(cx, vec![]);
+    let (trades, set_trades) = create_signal::<Vec<Trade>>(cx, vec![]);
+    let (positions, set_positions) = create_signal::<Vec<Position>>(cx, vec![]);
+    let push_order_feedback = move |feedback: OrderFeedback| {
+        set_order_feedback.update(|order_feedback| {
+            order_feedback.insert(0, feedback);
+            order_feedback.truncate(MAX_ORDER_FEEDBACK);
+        });
+    };
     let ws_client_sender = create_memo::<Option<SenderWrapper>>(cx, move |prev| {
         // Stop the previous ws connection
-        match prev {
-            Some(Some(x)) => {
-                let mut x = x.clone();
-                spawn_local(async move {
-                    let _ = x.sender.send(None).await;
-                });
-            }
-            _ => (),
-        };
+        if let Some(Some(x)) = prev {
+            let mut x = x.clone();
+            spawn_local(async move {
+                let _ = x.sender.send(None).await;
+            });
+        }
 
         if let Ok(ws_client) = WebSocket::open(&derived_ws_url()) {
             let (to_ws_sender, mut to_ws_recv) =
@@ -85,6 +217,9 @@ fn LadderViewInternal(cx: Scope, id: Memo<u32>) -> impl IntoView {
                     };
 
                     let mut to_ws_sender = to_ws_sender.clone();
+                    let mut ladder_sequence = LadderSequence::default();
+                    let mut trade_sequence = TradeSequence::default();
+                    let mut refused = false;
                     loop {
                         futures::select! {
                             msg = ws_client.next() => {
@@ -103,62 +238,130 @@ fn LadderViewInternal(cx: Scope, id: Memo<u32>) -> impl IntoView {
                                             ServerMessage::ConnectionInfo(latency) => {
                                                 set_latency(Some(latency));
                                             },
-                                            ServerMessage::TickSetWhole(set) => {
-                                                let res = set.into_iter().enumerate().map(|(idx, data)| {
-                                                    let tick_data = create_rw_signal(cx, data);
-                                                    let is_last_traded = create_rw_signal(cx, false);
-
-                                                    TickDataWrapper {
-                                                        id: idx,
-                                                        tick_data,
-                                                        is_last_traded,
-                                                    }
-                                                }).collect::<Vec<_>>();
+                                            ServerMessage::Selections(new_selections) => {
+                                                let is_known = |id| new_selections.iter().any(|selection| Some(selection.id) == id);
+                                                if !is_known(selected.get_untracked()) {
+                                                    set_selected(new_selections.first().map(|selection| selection.id));
+                                                }
+                                                set_selections(new_selections);
+                                            },
+                                            ServerMessage::TickSetWhole(snapshot) => {
+                                                ladder_sequence.reset(snapshot.sequence);
                                                 set_ladder.update(|ladder| {
-                                                    if ladder.len() != res.len() {
-                                                        *ladder = res;
+                                                    // Signals are only created when the ladder changes shape, the rows keep theirs
+                                                    if ladder.len() != snapshot.ticks.len() {
+                                                        *ladder = snapshot.ticks.into_iter().enumerate().map(|(idx, data)| {
+                                                            TickDataWrapper {
+                                                                id: idx,
+                                                                tick_data: create_rw_signal(cx, data),
+                                                                is_last_traded: create_rw_signal(cx, false),
+                                                            }
+                                                        }).collect();
                                                     } else {
-                                                        ladder.iter_mut().zip(res.iter()).for_each(|(prev, new)| {
-                                                            prev.tick_data.update(|prev| {
-                                                                *prev = new.tick_data.get_untracked();
-                                                            });
+                                                        ladder.iter_mut().zip(snapshot.ticks).for_each(|(prev, new)| {
+                                                            prev.tick_data.set(new);
+                                                            prev.is_last_traded.set(false);
                                                         });
                                                     }
+                                                    for latest_match in snapshot.latest_matches.iter() {
+                                                        set_latest_match(ladder, latest_match);
+                                                    }
                                                 });
                                             },
-                                            ServerMessage::TickUpdate(new_value) => {
-                                                set_ladder.update(|ladder| {
-                                                    ladder.iter_mut().for_each(|x| {
-                                                        if x.tick_data.get_untracked().tick == new_value.tick {
-                                                            x.tick_data.update(|prev| {
-                                                                *prev = new_value.clone();
-                                                            });
+                                            ServerMessage::LadderDelta(delta) => {
+                                                match ladder_sequence.check(delta.previous, delta.sequence) {
+                                                    SequenceCheck::Apply => set_ladder.update(|ladder| {
+                                                        for tick_data in delta.ticks.iter() {
+                                                            apply_tick_update(ladder, tick_data);
                                                         }
-                                                    });
-                                                });
-                                            },
-                                            ServerMessage::NewLatestMatch(new_value) => {
-                                                set_ladder.update(|ladder| {
-                                                    ladder.iter_mut().for_each(|x| {
-                                                        if x.tick_data.get_untracked().tick == new_value.tick {
-                                                            x.is_last_traded.update(|prev| {
-                                                                *prev = true;
-                                                            });
-                                                        } else {
-                                                            x.is_last_traded.update(|prev| {
-                                                                *prev = false;
-                                                            });
+                                                        for latest_match in delta.latest_matches.iter() {
+                                                            set_latest_match(ladder, latest_match);
                                                         }
-                                                    });
-                                                });
+                                                    }),
+                                                    SequenceCheck::Skip => {}
+                                                    SequenceCheck::Gap => {
+                                                        let _ = to_ws_sender.send(Some(TraderMessage::RequestSnapshot)).await;
+                                                    }
+                                                }
+                                            },
+                                            ServerMessage::Trade(trade) => {
+                                                match trade_sequence.check(trade.sequence) {
+                                                    SequenceCheck::Apply => set_trades.update(|trades| {
+                                                        trades.insert(0, trade);
+                                                        trades.truncate(MAX_TRADES);
+                                                    }),
+                                                    SequenceCheck::Skip => {}
+                                                    SequenceCheck::Gap => {
+                                                        let _ = to_ws_sender.send(Some(TraderMessage::RequestSnapshot)).await;
+                                                    }
+                                                }
+                                            },
+                                            ServerMessage::TradeHistory(mut history) => {
+                                                trade_sequence.reset(&history);
+                                                history.reverse();
+                                                history.truncate(MAX_TRADES);
+                                                set_trades(history);
+                                            },
+                                            ServerMessage::MarketStatus(status) => {
+                                                set_market_status(Some(status));
                                             },
                                             ServerMessage::OrderStateUpdate(new_order_state) => {
                                                 set_trader_orders.update(|order_state| {
                                                     *order_state = new_order_state;
                                                 });
                                             },
+                                            ServerMessage::TraderInfo(info) => {
+                                                set_trader_info(Some(info));
+                                            },
+                                            ServerMessage::Positions(new_positions) => {
+                                                set_positions(new_positions);
+                                            },
+                                            ServerMessage::Settlement(settlement) => {
+                                                set_statement.update(|statement| {
+                                                    statement.insert(0, settlement);
+                                                });
+                                            },
+                                            ServerMessage::OrderAccepted(request_id) => {
+                                                push_order_feedback(OrderFeedback {
+                                                    request_id,
+                                                    message: "Order accepted".to_string(),
+                                                    is_error: false,
+                                                });
+                                            },
+                                            ServerMessage::OrderRejected { request_id, reason } => {
+                                                push_order_feedback(OrderFeedback {
+                                                    request_id,
+                                                    message: format!("Order rejected: {reason}"),
+                                                    is_error: true,
+                                                });
+                                            },
+                                            ServerMessage::SelfTradePrevented(self_trade) => {
+                                                push_order_feedback(OrderFeedback {
+                                                    request_id: self_trade.incoming,
+                                                    message: format!(
+                                                        "Would have matched your own order, cancelled {} € of it and {} € of the resting order",
+                                                        self_trade.incoming_cancelled.0,
+                                                        self_trade.resting_cancelled.0,
+                                                    ),
+                                                    is_error: true,
+                                                });
+                                            },
+                                            ServerMessage::OrderFilled { request_id, price, matched, remaining } => {
+                                                push_order_feedback(OrderFeedback {
+                                                    request_id,
+                                                    message: format!("Matched {} € at {}, {} € remaining", matched.0, price.0, remaining.0),
+                                                    is_error: false,
+                                                });
+                                            },
                                         }
                                     }
+                                    Some(Err(WebSocketError::ConnectionClose(event))) => {
+                                        log!("WS closed by server: {} {}", event.code, event.reason);
+                                        // Another tab uses the same id, like a duplicated tab
+                                        // that copied the session storage
+                                        refused = event.code == TRADER_ALREADY_CONNECTED;
+                                        break
+                                    }
                                     _ => break, // don't act on text msgs
                                 }
                             }
@@ -167,7 +370,7 @@ fn LadderViewInternal(cx: Scope, id: Memo<u32>) -> impl IntoView {
                                     Some(Some(msg)) => {
                                         log!("Sending msg to server {:?}", msg);
                                         let mut writer = Vec::new();
-                                        if let Ok(_) = ciborium::into_writer(&msg, &mut writer) {
+                                        if ciborium::into_writer(&msg, &mut writer).is_ok() {
                                             let msg = Message::Bytes(writer);
                                             if ws_client.send(msg).await.is_err() {
                                                 break
@@ -181,9 +384,16 @@ fn LadderViewInternal(cx: Scope, id: Memo<u32>) -> impl IntoView {
                     }
                     interval.cancel();
                     set_latency(None);
+                    set_market_status(None);
                     set_ladder(vec![]);
+                    set_trades(vec![]);
+                    set_positions(vec![]);
                     let _ = ws_client.close().await;
                     log!("WS client closed");
+                    if refused {
+                        forget_trader_id();
+                        reconnect.notify();
+                    }
                 });
             }
             return Some(SenderWrapper { sender: to_ws_sender, url: derived_ws_url() })
@@ -197,21 +407,37 @@ fn LadderViewInternal(cx: Scope, id: Memo<u32>) -> impl IntoView {
 
     on_cleanup(cx, move || {
         log!("Running cleanup");
-        match ws_client_sender() {
-            Some(mut ws_client_sender) => {
-                spawn_local(async move {
-                    let _ = ws_client_sender.sender.send(None).await;
-                });
-            }
-            None => (),
+        if let Some(mut ws_client_sender) = ws_client_sender() {
+            spawn_local(async move {
+                let _ = ws_client_sender.sender.send(None).await;
+            });
         }
     });
 
     view! { cx,
         <div class="HomeView">
-            <StatsComponent latency=latency trader_orders=trader_orders/>
-            <OrderInformation trader_orders=trader_orders/>
-            <LadderTable ladder=ladder ws_client_sender=ws_client_sender/>
+            <StatsComponent
+                latency=latency
+                market_status=market_status
+                trader_info=trader_info
+                trader_orders=trader_orders
+            />
+            <OrderFeedbackList order_feedback=order_feedback/>
+            <OrderInformation
+                trader_orders=trader_orders
+                selections=selections
+                ws_client_sender=ws_client_sender
+            />
+            <Positions
+                positions=positions
+                selections=selections
+                ws_client_sender=ws_client_sender
+            />
+            <Statement statement=statement selections=selections/>
+            <TimeAndSales trades=trades selections=selections/>
+            <SelectionTabs selections=selections selected=selected set_selected=set_selected/>
+            <PriceChart market_id=id selected=selected/>
+            <LadderTable ladder=selected_ladder ws_client_sender=ws_client_sender/>
         </div>
     }
 }
@@ -231,13 +457,50 @@ fn derive_ws_url(id: u32) -> String {
             "ws"
         }
     };
-    format!("{}://{}/ws/{}", protocol, host, id)
+    format!("{}://{}/ws/{}?trader={}", protocol, host, id, trader_id())
+}
+
+/// Where the trader id is kept for the session of the tab
+const TRADER_ID_KEY: &str = "trader-id";
+
+/// The id the server knows the trader by. It is kept for the session of the tab, so that the
+/// trader keeps their balance after reloading the page. Open orders only survive the reload on
+/// markets that keep them for disconnected traders for a while.
+fn trader_id() -> String {
+    let storage = window().session_storage().ok().flatten();
+    let stored =
+        storage.as_ref().and_then(|storage| storage.get_item(TRADER_ID_KEY).ok().flatten());
+    if let Some(trader_id) = stored {
+        return trader_id
+    }
+    let trader_id = uuid::Uuid::new_v4().simple().to_string();
+    if let Some(storage) = storage {
+        let _ = storage.set_item(TRADER_ID_KEY, &trader_id);
+    }
+    trader_id
+}
+
+/// Drop the stored trader id, the next connection gets a new one
+fn forget_trader_id() {
+    if let Some(storage) = window().session_storage().ok().flatten() {
+        let _ = storage.remove_item(TRADER_ID_KEY);
+    }
+}
+
+fn selection_name(selections: &[Selection], id: SelectionId) -> String {
+    selections
+        .iter()
+        .find(|selection| selection.id == id)
+        .map(|selection| selection.name.clone())
+        .unwrap_or_else(|| format!("#{}", id.0))
 }
 
 #[component]
 fn StatsComponent(
     cx: Scope,
     latency: ReadSignal<Option<Latency>>,
+    market_status: ReadSignal<Option<MarketStatus>>,
+    trader_info: ReadSignal<Option<TraderInfo>>,
     trader_orders: ReadSignal<TraderOrders>,
 ) -> impl IntoView {
     view! { cx,
@@ -245,7 +508,7 @@ fn StatsComponent(
             <h3 class="text-base font-semibold leading-6 text-gray-700 mt-8 lg:mt-0 flex justify-center lg:justify-start">
                 "Stats"
             </h3>
-            <dl class="mt-6 grid grid-cols-3 divide-y divide-gray-200 overflow-hidden rounded-lg bg-white shadow md:grid-cols-3 md:divide-x md:divide-y-0">
+            <dl class="mt-6 grid grid-cols-2 divide-y divide-gray-200 overflow-hidden rounded-lg bg-white shadow md:grid-cols-3 md:divide-x md:divide-y-0">
                 <div class="px-4 py-5 sm:p-6">
                     <dt class="text-base font-normal text-gray-700">"WS Latency"</dt>
                     <dd class="mt-1 flex items-baseline justify-between md:block lg:flex">
@@ -262,6 +525,54 @@ fn StatsComponent(
                         </div>
                     </dd>
                 </div>
+                <div class="px-4 py-5 sm:p-6">
+                    <dt class="text-base font-normal text-gray-700">"Market"</dt>
+                    <dd class="mt-1 flex items-baseline justify-between md:block lg:flex">
+                        <div class="flex items-baseline text-2xl font-semibold text-indigo-600">
+                            {move || {
+                                market_status()
+                                    .map(|x| {
+                                        view! { cx, <span>{x.to_string()}</span> }
+                                    })
+                                    .unwrap_or_else(|| {
+                                        view! { cx, <span>"..."</span> }
+                                    })
+                            }}
+                        </div>
+                    </dd>
+                </div>
+                <div class="px-4 py-5 sm:p-6">
+                    <dt class="text-base font-normal text-gray-700">"Balance"</dt>
+                    <dd class="mt-1 flex items-baseline justify-between md:block lg:flex">
+                        <div class="flex items-baseline text-2xl font-semibold text-indigo-600">
+                            {move || {
+                                trader_info()
+                                    .map(|x| {
+                                        view! { cx, <span>{x.balance.0.round_dp(2).to_string()} " €"</span> }
+                                    })
+                                    .unwrap_or_else(|| {
+                                        view! { cx, <span>"..."</span> }
+                                    })
+                            }}
+                        </div>
+                    </dd>
+                </div>
+                <div class="px-4 py-5 sm:p-6">
+                    <dt class="text-base font-normal text-gray-700">"Exposure"</dt>
+                    <dd class="mt-1 flex items-baseline justify-between md:block lg:flex">
+                        <div class="flex items-baseline text-2xl font-semibold text-indigo-600">
+                            {move || {
+                                trader_info()
+                                    .map(|x| {
+                                        view! { cx, <span>{x.exposure.0.round_dp(2).to_string()} " €"</span> }
+                                    })
+                                    .unwrap_or_else(|| {
+                                        view! { cx, <span>"..."</span> }
+                                    })
+                            }}
+                        </div>
+                    </dd>
+                </div>
                 <div class="px-4 py-5 sm:p-6">
                     <dt class="text-base font-normal text-gray-700">"Matched backs"</dt>
                     <dd class="mt-1 flex items-baseline justify-between md:block lg:flex">
@@ -269,10 +580,9 @@ fn StatsComponent(
                             {move || {
                                 let orders = trader_orders();
                                 let matched = orders
-                                    .matched_orders
-                                    .iter()
-                                    .filter(|(_, x)| x.side == Side::Back)
-                                    .fold(dec!(0), |acc, (_, x)| acc + x.size.0);
+                                    .matched()
+                                    .filter(|x| x.side == Side::Back)
+                                    .fold(dec!(0), |acc, x| acc + x.matched_size.0);
                                 view! { cx, <span>{matched.to_string()} " €"</span> }
                             }}
                         </div>
@@ -285,10 +595,9 @@ fn StatsComponent(
                             {move || {
                                 let orders = trader_orders();
                                 let matched = orders
-                                    .matched_orders
-                                    .iter()
-                                    .filter(|(_, x)| x.side == Side::Lay)
-                                    .fold(dec!(0), |acc, (_, x)| acc + x.size.0);
+                                    .matched()
+                                    .filter(|x| x.side == Side::Lay)
+                                    .fold(dec!(0), |acc, x| acc + x.matched_size.0);
                                 view! { cx, <span>{matched.to_string()} " €"</span> }
                             }}
                         </div>
@@ -300,21 +609,70 @@ fn StatsComponent(
 }
 
 #[component]
-fn OrderInformation(cx: Scope, trader_orders: ReadSignal<TraderOrders>) -> impl IntoView {
+fn OrderFeedbackList(cx: Scope, order_feedback: ReadSignal<Vec<OrderFeedback>>) -> impl IntoView {
+    view! { cx,
+        <ul class="mt-6 space-y-1 text-sm">
+            <For
+                each=order_feedback
+                key=|feedback| (feedback.request_id.clone(), feedback.message.clone())
+                view=move |cx, feedback| {
+                    let class = if feedback.is_error { "text-red-700" } else { "text-gray-700" };
+                    view! { cx,
+                        <li class=class>
+                            <span class="font-mono text-gray-400">
+                                {feedback.request_id.0.chars().take(8).collect::<String>()}
+                            </span>
+                            " "
+                            {feedback.message}
+                        </li>
+                    }
+                }
+            />
+        </ul>
+    }
+}
+
+#[component]
+fn OrderInformation(
+    cx: Scope,
+    trader_orders: ReadSignal<TraderOrders>,
+    selections: ReadSignal<Vec<Selection>>,
+    ws_client_sender: Memo<Option<SenderWrapper>>,
+) -> impl IntoView {
+    let send_msg = move |msg: TraderMessage| {
+        if let Some(mut sender) = ws_client_sender() {
+            spawn_local(async move {
+                let _ = sender.sender.send(Some(msg)).await;
+            });
+        }
+    };
+
     view! { cx,
         <div>
-            <div class="flex mt-[3.5rem] items-center justify-center lg:justify-start">
+            <div class="flex mt-[3.5rem] items-center justify-between">
                 <div class="flex-col">
                     <h1 class="text-base font-semibold leading-6 text-gray-700 flex justify-center lg:justify-start">
                         "Unmatched bets"
                     </h1>
                     <p class="mt-2 text-sm text-gray-700">"A list of all unmatched orders"</p>
                 </div>
+                <button
+                    class="rounded bg-white px-2 py-1 text-sm font-semibold text-gray-700 shadow-sm ring-1 ring-inset ring-gray-300 hover:bg-gray-50"
+                    on:click=move |_| send_msg(TraderMessage::CancelAll)
+                >
+                    "Cancel all"
+                </button>
             </div>
             <div class="mt-6">
                 <table class="min-w-full divide-y divide-gray-300">
                     <thead>
                         <tr>
+                            <th
+                                scope="col"
+                                class=" px-3 py-3.5 text-left text-sm font-semibold text-gray-700 sm:table-cell"
+                            >
+                                "Selection"
+                            </th>
                             <th
                                 scope="col"
                                 class=" px-3 py-3.5 text-left text-sm font-semibold text-gray-700 sm:table-cell"
@@ -333,17 +691,30 @@ fn OrderInformation(cx: Scope, trader_orders: ReadSignal<TraderOrders>) -> impl
                             >
                                 "Size"
                             </th>
+                            <th
+                                scope="col"
+                                class=" px-3 py-3.5 text-left text-sm font-semibold text-gray-700 lg:table-cell"
+                            >
+                                "Matched"
+                            </th>
+                            <th scope="col" class="relative py-3.5 pl-3 pr-4 sm:pr-0">
+                                <span class="sr-only">"Cancel"</span>
+                            </th>
                         </tr>
                     </thead>
                     <tbody class="divide-y divide-gray-200 bg-white">
                         {move || {
+                            let selections = selections();
                             let mut res = trader_orders()
-                                .unmatched_orders
-                                .values()
+                                .unmatched()
                                 .map(|order| {
+                                    let request_id = order.request_id.clone();
                                     (
                                         view! { cx,
                                             <tr>
+                                                <td class="whitespace-nowrap px-3 py-4 text-sm text-gray-500 sm:table-cell">
+                                                    {selection_name(&selections, order.selection)}
+                                                </td>
                                                 <td class="whitespace-nowrap px-3 py-4 text-sm text-gray-500 sm:table-cell">
                                                     {order.tick.0.to_string()}
                                                 </td>
@@ -351,7 +722,22 @@ fn OrderInformation(cx: Scope, trader_orders: ReadSignal<TraderOrders>) -> impl
                                                     {order.side.to_string()}
                                                 </td>
                                                 <td class="whitespace-nowrap px-3 py-4 text-sm text-gray-500 sm:table-cell">
-                                                    {order.size.0.to_string()}
+                                                    {order.remaining_size.0.to_string()}
+                                                    " / "
+                                                    {order.original_size.0.to_string()}
+                                                </td>
+                                                <td class="whitespace-nowrap px-3 py-4 text-sm text-gray-500 sm:table-cell">
+                                                    {order.matched_size.0.to_string()}
+                                                </td>
+                                                <td class="whitespace-nowrap py-4 pl-3 pr-4 text-right text-sm font-medium sm:pr-0">
+                                                    <button
+                                                        class="text-indigo-600 hover:text-indigo-900"
+                                                        on:click=move |_| {
+                                                            send_msg(TraderMessage::CancelOrder(request_id.clone()))
+                                                        }
+                                                    >
+                                                        "Cancel"
+                                                    </button>
                                                 </td>
                                             </tr>
                                         },
@@ -362,13 +748,16 @@ fn OrderInformation(cx: Scope, trader_orders: ReadSignal<TraderOrders>) -> impl
                             if !res.is_empty() {
                                 res.sort_by(|(_, order1), (_, order2)| {
                                     order1
-                                        .tick
-                                        .cmp(&order2.tick)
+                                        .selection
+                                        .cmp(&order2.selection)
+                                        .then_with(|| order1.tick.cmp(&order2.tick))
                                         .then_with(|| {
                                             order1
                                                 .side
                                                 .cmp(&order2.side)
-                                                .then_with(|| { order1.size.cmp(&order2.size) })
+                                                .then_with(|| {
+                                                    order1.remaining_size.cmp(&order2.remaining_size)
+                                                })
                                         })
                                 });
                                 res.into_iter().map(|(x, _)| x).collect::<Vec<_>>()
@@ -387,6 +776,343 @@ fn OrderInformation(cx: Scope, trader_orders: ReadSignal<TraderOrders>) -> impl
     }
 }
 
+#[component]
+fn Positions(
+    cx: Scope,
+    positions: ReadSignal<Vec<Position>>,
+    selections: ReadSignal<Vec<Selection>>,
+    ws_client_sender: Memo<Option<SenderWrapper>>,
+) -> impl IntoView {
+    let send_msg = move |msg: TraderMessage| {
+        if let Some(mut sender) = ws_client_sender() {
+            spawn_local(async move {
+                let _ = sender.sender.send(Some(msg)).await;
+            });
+        }
+    };
+    let format_price = |price: Option<rust_decimal::Decimal>| {
+        price.map(|price| price.round_dp(2).to_string()).unwrap_or_else(|| "-".to_string())
+    };
+
+    view! { cx,
+        <div>
+            <div class="flex mt-[3.5rem] items-center justify-center lg:justify-start">
+                <div class="flex-col">
+                    <h1 class="text-base font-semibold leading-6 text-gray-700 flex justify-center lg:justify-start">
+                        "Positions"
+                    </h1>
+                    <p class="mt-2 text-sm text-gray-700">"Matched bets per selection"</p>
+                </div>
+            </div>
+            <div class="mt-6">
+                <table class="min-w-full divide-y divide-gray-300">
+                    <thead>
+                        <tr>
+                            <th
+                                scope="col"
+                                class=" px-3 py-3.5 text-left text-sm font-semibold text-gray-700 sm:table-cell"
+                            >
+                                "Selection"
+                            </th>
+                            <th
+                                scope="col"
+                                class=" px-3 py-3.5 text-left text-sm font-semibold text-gray-700 sm:table-cell"
+                            >
+                                "Backed"
+                            </th>
+                            <th
+                                scope="col"
+                                class=" px-3 py-3.5 text-left text-sm font-semibold text-gray-700 sm:table-cell"
+                            >
+                                "Lay liability"
+                            </th>
+                            <th
+                                scope="col"
+                                class=" px-3 py-3.5 text-left text-sm font-semibold text-gray-700 sm:table-cell"
+                            >
+                                "Avg odds"
+                            </th>
+                            <th
+                                scope="col"
+                                class=" px-3 py-3.5 text-left text-sm font-semibold text-gray-700 sm:table-cell"
+                            >
+                                "If wins"
+                            </th>
+                            <th
+                                scope="col"
+                                class=" px-3 py-3.5 text-left text-sm font-semibold text-gray-700 sm:table-cell"
+                            >
+                                "If loses"
+                            </th>
+                            <th scope="col" class="relative py-3.5 pl-3 pr-4 sm:pr-0">
+                                <span class="sr-only">"Cash out"</span>
+                            </th>
+                        </tr>
+                    </thead>
+                    <tbody class="divide-y divide-gray-200 bg-white">
+                        {move || {
+                            let selections = selections();
+                            positions()
+                                .into_iter()
+                                .map(|position| {
+                                    let selection = position.selection;
+                                    view! { cx,
+                                        <tr>
+                                            <td class="whitespace-nowrap px-3 py-4 text-sm text-gray-500 sm:table-cell">
+                                                {selection_name(&selections, selection)}
+                                            </td>
+                                            <td class="whitespace-nowrap px-3 py-4 text-sm text-gray-500 sm:table-cell">
+                                                {position.back_stake.0.round_dp(2).to_string()}
+                                                " €"
+                                            </td>
+                                            <td class="whitespace-nowrap px-3 py-4 text-sm text-gray-500 sm:table-cell">
+                                                {position.lay_liability.0.round_dp(2).to_string()}
+                                                " €"
+                                            </td>
+                                            <td class="whitespace-nowrap px-3 py-4 text-sm text-gray-500 sm:table-cell">
+                                                {format_price(position.average_back_price)}
+                                                " / "
+                                                {format_price(position.average_lay_price)}
+                                            </td>
+                                            <td class="whitespace-nowrap px-3 py-4 text-sm text-gray-500 sm:table-cell">
+                                                {position.profit_if_wins.round_dp(2).to_string()}
+                                                " €"
+                                            </td>
+                                            <td class="whitespace-nowrap px-3 py-4 text-sm text-gray-500 sm:table-cell">
+                                                {position.profit_if_loses.round_dp(2).to_string()}
+                                                " €"
+                                            </td>
+                                            <td class="whitespace-nowrap py-4 pl-3 pr-4 text-right text-sm font-medium sm:pr-0">
+                                                {position
+                                                    .cash_out
+                                                    .map(|hedge| {
+                                                        view! { cx,
+                                                            <button
+                                                                class="text-indigo-600 hover:text-indigo-900"
+                                                                on:click=move |_| send_msg(TraderMessage::CashOut(selection))
+                                                            >
+                                                                "Cash out " {hedge.profit.round_dp(2).to_string()} " € ("
+                                                                {hedge.side.to_string()} " " {hedge.size.0.to_string()}
+                                                                " @ " {hedge.price.0.to_string()} ")"
+                                                            </button>
+                                                        }
+                                                    })}
+                                            </td>
+                                        </tr>
+                                    }
+                                })
+                                .collect::<Vec<_>>()
+                        }}
+                    </tbody>
+                </table>
+            </div>
+        </div>
+    }
+}
+
+#[component]
+fn Statement(
+    cx: Scope,
+    statement: ReadSignal<Vec<Settlement>>,
+    selections: ReadSignal<Vec<Selection>>,
+) -> impl IntoView {
+    view! { cx,
+        <div>
+            <div class="flex mt-[3.5rem] items-center justify-center lg:justify-start">
+                <div class="flex-col">
+                    <h1 class="text-base font-semibold leading-6 text-gray-700 flex justify-center lg:justify-start">
+                        "Statement"
+                    </h1>
+                    <p class="mt-2 text-sm text-gray-700">"Profit and loss of every settled round"</p>
+                </div>
+            </div>
+            <div class="mt-6">
+                <table class="min-w-full divide-y divide-gray-300">
+                    <thead>
+                        <tr>
+                            <th
+                                scope="col"
+                                class=" px-3 py-3.5 text-left text-sm font-semibold text-gray-700 sm:table-cell"
+                            >
+                                "Winner"
+                            </th>
+                            <th
+                                scope="col"
+                                class=" px-3 py-3.5 text-left text-sm font-semibold text-gray-700 sm:table-cell"
+                            >
+                                "Profit / loss"
+                            </th>
+                            <th
+                                scope="col"
+                                class=" px-3 py-3.5 text-left text-sm font-semibold text-gray-700 sm:table-cell"
+                            >
+                                "Commission"
+                            </th>
+                            <th
+                                scope="col"
+                                class=" px-3 py-3.5 text-left text-sm font-semibold text-gray-700 sm:table-cell"
+                            >
+                                "Balance"
+                            </th>
+                        </tr>
+                    </thead>
+                    <tbody class="divide-y divide-gray-200 bg-white">
+                        {move || {
+                            let selections = selections();
+                            statement()
+                                .into_iter()
+                                .map(|settlement| {
+                                    view! { cx,
+                                        <tr>
+                                            <td class="whitespace-nowrap px-3 py-4 text-sm text-gray-500 sm:table-cell">
+                                                {selection_name(&selections, settlement.winner)}
+                                            </td>
+                                            <td class="whitespace-nowrap px-3 py-4 text-sm text-gray-500 sm:table-cell">
+                                                {settlement.profit_and_loss.0.round_dp(2).to_string()}
+                                                " €"
+                                            </td>
+                                            <td class="whitespace-nowrap px-3 py-4 text-sm text-gray-500 sm:table-cell">
+                                                {settlement.commission.0.round_dp(2).to_string()}
+                                                " €"
+                                            </td>
+                                            <td class="whitespace-nowrap px-3 py-4 text-sm text-gray-500 sm:table-cell">
+                                                {settlement.balance.0.round_dp(2).to_string()}
+                                                " €"
+                                            </td>
+                                        </tr>
+                                    }
+                                })
+                                .collect::<Vec<_>>()
+                        }}
+                    </tbody>
+                </table>
+            </div>
+        </div>
+    }
+}
+
+#[component]
+fn TimeAndSales(
+    cx: Scope,
+    trades: ReadSignal<Vec<Trade>>,
+    selections: ReadSignal<Vec<Selection>>,
+) -> impl IntoView {
+    view! { cx,
+        <div>
+            <div class="flex mt-[3.5rem] items-center justify-center lg:justify-start">
+                <div class="flex-col">
+                    <h1 class="text-base font-semibold leading-6 text-gray-700 flex justify-center lg:justify-start">
+                        "Time and sales"
+                    </h1>
+                    <p class="mt-2 text-sm text-gray-700">"The latest trades of the market"</p>
+                </div>
+            </div>
+            <div class="mt-6 max-h-[24rem] overflow-auto">
+                <table class="min-w-full divide-y divide-gray-300">
+                    <thead>
+                        <tr>
+                            <th
+                                scope="col"
+                                class=" px-3 py-3.5 text-left text-sm font-semibold text-gray-700 sm:table-cell"
+                            >
+                                "Time"
+                            </th>
+                            <th
+                                scope="col"
+                                class=" px-3 py-3.5 text-left text-sm font-semibold text-gray-700 sm:table-cell"
+                            >
+                                "Selection"
+                            </th>
+                            <th
+                                scope="col"
+                                class=" px-3 py-3.5 text-left text-sm font-semibold text-gray-700 sm:table-cell"
+                            >
+                                "Price"
+                            </th>
+                            <th
+                                scope="col"
+                                class=" px-3 py-3.5 text-left text-sm font-semibold text-gray-700 sm:table-cell"
+                            >
+                                "Size"
+                            </th>
+                            <th
+                                scope="col"
+                                class=" px-3 py-3.5 text-left text-sm font-semibold text-gray-700 sm:table-cell"
+                            >
+                                "Aggressor"
+                            </th>
+                        </tr>
+                    </thead>
+                    <tbody class="divide-y divide-gray-200 bg-white">
+                        {move || {
+                            let selections = selections();
+                            trades()
+                                .into_iter()
+                                .map(|trade| {
+                                    let class = match trade.aggressor_side {
+                                        Side::Back => "whitespace-nowrap px-3 py-4 text-sm text-blue-950 bg-blue-200",
+                                        Side::Lay => "whitespace-nowrap px-3 py-4 text-sm text-red-950 bg-red-200",
+                                    };
+                                    view! { cx,
+                                        <tr>
+                                            <td class="whitespace-nowrap px-3 py-4 text-sm text-gray-500 sm:table-cell">
+                                                {trade.timestamp.format("%H:%M:%S").to_string()}
+                                            </td>
+                                            <td class="whitespace-nowrap px-3 py-4 text-sm text-gray-500 sm:table-cell">
+                                                {selection_name(&selections, trade.selection)}
+                                            </td>
+                                            <td class="whitespace-nowrap px-3 py-4 text-sm text-gray-500 sm:table-cell">
+                                                {trade.price.0.to_string()}
+                                            </td>
+                                            <td class="whitespace-nowrap px-3 py-4 text-sm text-gray-500 sm:table-cell">
+                                                {trade.size.0.to_string()}
+                                                " €"
+                                            </td>
+                                            <td class=class>{trade.aggressor_side.to_string()}</td>
+                                        </tr>
+                                    }
+                                })
+                                .collect::<Vec<_>>()
+                        }}
+                    </tbody>
+                </table>
+            </div>
+        </div>
+    }
+}
+
+#[component]
+fn SelectionTabs(
+    cx: Scope,
+    selections: ReadSignal<Vec<Selection>>,
+    selected: ReadSignal<Option<SelectionId>>,
+    set_selected: WriteSignal<Option<SelectionId>>,
+) -> impl IntoView {
+    view! { cx,
+        <nav class="mt-[3.5rem] flex space-x-4 justify-center lg:justify-start">
+            <For
+                each=selections
+                key=|selection| selection.id
+                view=move |cx, selection| {
+                    let id = selection.id;
+                    let class = move || {
+                        if selected() == Some(id) {
+                            "rounded-md px-3 py-2 text-sm font-medium bg-indigo-100 text-indigo-700"
+                        } else {
+                            "rounded-md px-3 py-2 text-sm font-medium text-gray-500 hover:text-gray-700"
+                        }
+                    };
+                    view! { cx,
+                        <button class=class on:click=move |_| set_selected(Some(id))>
+                            {selection.name}
+                        </button>
+                    }
+                }
+            />
+        </nav>
+    }
+}
+
 #[component]
 fn LadderTable(
     cx: Scope,
@@ -405,7 +1131,7 @@ fn LadderTable(
             </div>
             <div class="px-4 sm:px-6 lg:px-8">
                 <div class="mt-8 flow-root">
-                    <div class="-mx-4 -my-2 overflow-x-auto sm:-mx-6 lg:-mx-8">
+                    <div class="-mx-4 -my-2 max-h-[48rem] overflow-auto sm:-mx-6 lg:-mx-8">
                         <table class="table-fixed w-full divide-y divide-gray-300">
                             <thead>
                                 <tr class="divide-x divide-gray-200">
@@ -487,8 +1213,8 @@ fn TickRow(
         let Ok(value) = value.parse::<rust_decimal::Decimal>() else {
             return;
         };
-        let tick = data.tick_data.get().tick;
-        let order = Order { tick, size: Size(value), side };
+        let TickData { selection, tick, .. } = data.tick_data.get();
+        let order = Order { selection, tick, size: Size(value), side };
 
         let sender = ws_client_sender();
         if let Some(mut sender) = sender {
@@ -0,0 +1,153 @@
+use leptos::*;
+use rust_decimal::prelude::ToPrimitive;
+use trading_types::common::SelectionId;
+use trading_types::from_server::{Candle, CandleInterval};
+
+/// How often the candles are fetched again
+const REFRESH_INTERVAL_MS: u32 = 5_000;
+const CHART_WIDTH: f64 = 600.0;
+const CHART_HEIGHT: f64 = 200.0;
+
+#[component]
+pub fn PriceChart(
+    cx: Scope,
+    market_id: Memo<u32>,
+    selected: ReadSignal<Option<SelectionId>>,
+) -> impl IntoView {
+    let (interval, set_interval) = create_signal(cx, CandleInterval::TenSeconds);
+    let (candles, set_candles) = create_signal::<Vec<Candle>>(cx, vec![]);
+
+    let fetch_candles = move |market_id: u32, interval: CandleInterval| {
+        spawn_local(async move {
+            let url = format!("/candles/{market_id}?interval={interval}");
+            let Ok(response) = gloo_net::http::Request::get(&url).send().await else {
+                return;
+            };
+            if let Ok(new_candles) = response.json::<Vec<Candle>>().await {
+                set_candles(new_candles);
+            }
+        });
+    };
+    create_effect(cx, move |prev: Option<gloo_timers::callback::Interval>| {
+        // Dropping the previous timer stops it
+        drop(prev);
+        let (market_id, interval) = (market_id(), interval());
+        fetch_candles(market_id, interval);
+        gloo_timers::callback::Interval::new(REFRESH_INTERVAL_MS, move || {
+            fetch_candles(market_id, interval)
+        })
+    });
+
+    let selected_candles = move || {
+        candles()
+            .into_iter()
+            .filter(|candle| Some(candle.selection) == selected())
+            .collect::<Vec<_>>()
+    };
+
+    view! { cx,
+        <div>
+            <div class="flex mt-[3.5rem] items-center justify-between">
+                <div class="flex-col">
+                    <h1 class="text-base font-semibold leading-6 text-gray-700 flex justify-center lg:justify-start">
+                        "Price chart"
+                    </h1>
+                    <p class="mt-2 text-sm text-gray-700">"Matched prices of the selection over time"</p>
+                </div>
+                <div class="flex space-x-2">
+                    {CandleInterval::ALL
+                        .into_iter()
+                        .map(|option| {
+                            let class = move || {
+                                if interval() == option {
+                                    "rounded-md px-3 py-2 text-sm font-medium bg-indigo-100 text-indigo-700"
+                                } else {
+                                    "rounded-md px-3 py-2 text-sm font-medium text-gray-500 hover:text-gray-700"
+                                }
+                            };
+                            view! { cx,
+                                <button class=class on:click=move |_| set_interval(option)>
+                                    {option.to_string()}
+                                </button>
+                            }
+                        })
+                        .collect_view(cx)}
+                </div>
+            </div>
+            <div class="mt-6">
+                {move || {
+                    let candles = selected_candles();
+                    if candles.is_empty() {
+                        return view! { cx, <p class="text-sm text-gray-500">"No trades yet"</p> }
+                            .into_view(cx);
+                    }
+                    view! { cx,
+                        <svg
+                            class="w-full bg-white"
+                            viewBox=format!("0 0 {CHART_WIDTH} {CHART_HEIGHT}")
+                            preserveAspectRatio="none"
+                        >
+                            {candle_shapes(cx, &candles)}
+                        </svg>
+                        {candles
+                            .last()
+                            .map(|candle| {
+                                view! { cx,
+                                    <p class="mt-2 text-sm text-gray-700">
+                                        "Last " {candle.close.0.to_string()} ", VWAP "
+                                        {candle.vwap.round_dp(2).to_string()} ", volume "
+                                        {candle.volume.0.to_string()} " €"
+                                    </p>
+                                }
+                            })}
+                    }
+                        .into_view(cx)
+                }}
+            </div>
+        </div>
+    }
+}
+
+/// Wicks, bodies and the VWAP line of the candles scaled to the chart
+fn candle_shapes(cx: Scope, candles: &[Candle]) -> View {
+    let price = |value: rust_decimal::Decimal| value.to_f64().unwrap_or_default();
+    let high = candles.iter().map(|candle| price(candle.high.0)).fold(f64::MIN, f64::max);
+    let low = candles.iter().map(|candle| price(candle.low.0)).fold(f64::MAX, f64::min);
+    let range = if high > low { high - low } else { 1.0 };
+    let y = move |value: f64| CHART_HEIGHT - (value - low) / range * (CHART_HEIGHT - 10.0) - 5.0;
+    let width = CHART_WIDTH / candles.len() as f64;
+
+    let vwap_points = candles
+        .iter()
+        .enumerate()
+        .map(|(idx, candle)| format!("{},{}", (idx as f64 + 0.5) * width, y(price(candle.vwap))))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let bodies = candles
+        .iter()
+        .enumerate()
+        .map(|(idx, candle)| {
+            let (open, close) = (price(candle.open.0), price(candle.close.0));
+            let color = if close >= open { "#16a34a" } else { "#dc2626" };
+            let center = (idx as f64 + 0.5) * width;
+            let top = y(open.max(close));
+            let height = (y(open.min(close)) - top).max(1.0);
+            view! { cx,
+                <line
+                    x1=center
+                    x2=center
+                    y1=y(price(candle.high.0))
+                    y2=y(price(candle.low.0))
+                    stroke=color
+                />
+                <rect x=center - width * 0.3 y=top width=width * 0.6 height=height fill=color></rect>
+            }
+        })
+        .collect_view(cx);
+
+    view! { cx,
+        {bodies}
+        <polyline points=vwap_points fill="none" stroke="#4f46e5" stroke-width="1"></polyline>
+    }
+    .into_view(cx)
+}
@@ -1,13 +1,20 @@
-use axum::extract::{Path, State, WebSocketUpgrade};
+use std::collections::HashMap;
+
+use axum::extract::{Path, Query, State, WebSocketUpgrade};
 use axum::response::IntoResponse;
 use state::WebAppState;
 
+mod outbox;
 mod ws;
 
+/// Upgrade to the websocket of the market. A client that sends the `trader` it connected with
+/// before keeps its account and orders.
 pub async fn handler(
     ws: WebSocketUpgrade,
     Path(market_id): Path<u32>,
+    Query(mut params): Query<HashMap<String, String>>,
     State(state): State<WebAppState>,
 ) -> impl IntoResponse {
-    ws.on_upgrade(move |ws| ws::handle_connection(state, ws, market_id))
+    let trader_id = ws::trader_id(params.remove("trader"));
+    ws.on_upgrade(move |ws| ws::handle_connection(state, ws, market_id, trader_id))
 }
@@ -0,0 +1,198 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use axum::extract::ws::Message;
+
+/// How many market data messages can wait for a connection before they get dropped
+const MARKET_DATA_LIMIT: usize = 64;
+
+/// How many messages can wait for a connection before it counts as falling behind
+const QUEUE_LIMIT: usize = 256;
+
+/// Messages waiting to be written to a single websocket, in order
+#[derive(Debug, Default)]
+pub struct Outbox {
+    queue: VecDeque<Queued>,
+    /// How many of the queued messages are market data
+    market_data: usize,
+    /// Market data got dropped, so the trader needs the whole market state again
+    resync: bool,
+    /// When the connection started falling behind, cleared once everything got written
+    behind_since: Option<Instant>,
+    closed: bool,
+}
+
+#[derive(Debug)]
+struct Queued {
+    message: Message,
+    market_data: bool,
+}
+
+impl Outbox {
+    /// Queue a message that must not get lost, like an order acknowledgement
+    pub fn push(&mut self, message: Message) {
+        if self.closed {
+            return
+        }
+        self.queue.push_back(Queued { message, market_data: false });
+        if self.queue.len() > QUEUE_LIMIT {
+            self.falling_behind();
+        }
+    }
+
+    /// Queue market data, dropping it when the connection can't keep up. Dropped market data is
+    /// made up for by sending the whole market state once the queue got written.
+    pub fn push_market_data(&mut self, message: Message) {
+        if self.closed || self.resync {
+            return
+        }
+        if self.market_data >= MARKET_DATA_LIMIT {
+            self.drop_market_data();
+            return
+        }
+        self.queue.push_back(Queued { message, market_data: true });
+        self.market_data += 1;
+    }
+
+    /// Forget the queued market data, as some of it got lost anyway
+    pub fn drop_market_data(&mut self) {
+        self.queue.retain(|queued| !queued.market_data);
+        self.market_data = 0;
+        self.resync = true;
+        self.falling_behind();
+    }
+
+    fn falling_behind(&mut self) {
+        self.behind_since.get_or_insert_with(Instant::now);
+    }
+
+    pub fn pop(&mut self) -> Option<Message> {
+        let queued = self.queue.pop_front()?;
+        if queued.market_data {
+            self.market_data -= 1;
+        }
+        if self.queue.is_empty() && !self.resync {
+            self.behind_since = None;
+        }
+        Some(queued.message)
+    }
+
+    /// Whether the market state has to be sent again, which is only done once everything queued
+    /// got written
+    pub fn take_resync(&mut self) -> bool {
+        if !self.queue.is_empty() || !self.resync {
+            return false
+        }
+        self.resync = false;
+        self.behind_since = None;
+        true
+    }
+
+    /// How long the connection has not been keeping up
+    pub fn behind_for(&self) -> Option<Duration> {
+        self.behind_since.map(|since| since.elapsed())
+    }
+
+    /// Replace everything queued with the close message, nothing else gets queued afterwards
+    pub fn close(&mut self, message: Message) {
+        self.queue.clear();
+        self.queue.push_back(Queued { message, market_data: false });
+        self.market_data = 0;
+        self.resync = false;
+        self.behind_since = None;
+        self.closed = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(text: &str) -> Message {
+        Message::Text(text.to_string())
+    }
+
+    fn drain(outbox: &mut Outbox) -> Vec<Message> {
+        std::iter::from_fn(|| outbox.pop()).collect()
+    }
+
+    #[test]
+    fn market_data_past_the_limit_is_dropped_for_a_resync() {
+        let mut outbox = Outbox::default();
+        for _ in 0..MARKET_DATA_LIMIT {
+            outbox.push_market_data(message("ladder"));
+        }
+        assert!(outbox.behind_for().is_none());
+
+        outbox.push_market_data(message("ladder"));
+        assert!(outbox.behind_for().is_some());
+        // Stale market data is not queued while the resync is pending
+        outbox.push_market_data(message("ladder"));
+        assert!(drain(&mut outbox).is_empty());
+        assert!(outbox.take_resync());
+        assert!(!outbox.take_resync());
+
+        outbox.push_market_data(message("ladder"));
+        assert_eq!(drain(&mut outbox), [message("ladder")]);
+    }
+
+    #[test]
+    fn acks_are_never_dropped() {
+        let mut outbox = Outbox::default();
+        outbox.push(message("ack-1"));
+        for _ in 0..=MARKET_DATA_LIMIT {
+            outbox.push_market_data(message("ladder"));
+        }
+        outbox.push(message("ack-2"));
+        for _ in 0..QUEUE_LIMIT {
+            outbox.push(message("ack"));
+        }
+
+        let messages = drain(&mut outbox);
+        assert_eq!(messages.len(), QUEUE_LIMIT + 2);
+        assert_eq!(messages[..2], [message("ack-1"), message("ack-2")]);
+    }
+
+    #[test]
+    fn resync_waits_for_the_queue_to_empty() {
+        let mut outbox = Outbox::default();
+        outbox.push(message("ack"));
+        outbox.drop_market_data();
+
+        assert!(!outbox.take_resync());
+        assert_eq!(outbox.pop(), Some(message("ack")));
+        // Still behind until the market state is sent again
+        assert!(outbox.behind_for().is_some());
+        assert!(outbox.take_resync());
+        assert!(outbox.behind_for().is_none());
+    }
+
+    #[test]
+    fn catching_up_clears_behind() {
+        let mut outbox = Outbox::default();
+        for _ in 0..=QUEUE_LIMIT {
+            outbox.push(message("ack"));
+        }
+        assert!(outbox.behind_for().is_some());
+
+        outbox.pop();
+        assert!(outbox.behind_for().is_some());
+        drain(&mut outbox);
+        assert!(outbox.behind_for().is_none());
+        assert!(!outbox.take_resync());
+    }
+
+    #[test]
+    fn nothing_is_queued_after_closing() {
+        let mut outbox = Outbox::default();
+        outbox.push(message("ack"));
+        outbox.push_market_data(message("ladder"));
+        outbox.close(Message::Close(None));
+
+        outbox.push(message("ack"));
+        outbox.push_market_data(message("ladder"));
+        assert_eq!(drain(&mut outbox), [Message::Close(None)]);
+        assert!(outbox.behind_for().is_none());
+        assert!(!outbox.take_resync());
+    }
+}
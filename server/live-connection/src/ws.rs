@@ -1,4 +1,4 @@
-use std::rc::Rc;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use actix::{
@@ -6,21 +6,43 @@ use actix::{
     Handler, StreamHandler, WrapFuture,
 };
 use anyhow::anyhow;
-use axum::extract::ws::{self, WebSocket};
+use axum::extract::ws::{self, CloseFrame, WebSocket};
 use futures::stream::SplitSink;
 use futures::{SinkExt, StreamExt};
 use state::WebAppState;
-use tokio::sync::Mutex;
-use trading_logic::market::messages::{OrderStateUpdate, RegisterTrader, TickDataUpdate};
-use trading_logic::market::MarketActor;
-use trading_types::common::TraderId;
-use trading_types::from_server::{Latency, ServerMessage};
+use trading_logic::market::messages::{
+    AlreadyConnected, CancelOrder, CancelOrders, CashOut, DeregisterTrader, OrderFill, OrderPlaced,
+    PlaceOrder, RegisterTrader, RequestMarketState, TickDataUpdate, TraderUpdate,
+};
+use trading_logic::market::{MarketActor, MarketData};
+use trading_types::common::{RequestId, TraderId};
+use trading_types::from_server::{Latency, RejectReason, ServerMessage, TRADER_ALREADY_CONNECTED};
 use trading_types::from_trader::TraderMessage;
 
+use crate::outbox::Outbox;
+
+/// Shortest and longest id a client may pick for itself, long enough not to be guessed or to clash
+/// with the ids of the bots
+const TRADER_ID_LENGTH: std::ops::RangeInclusive<usize> = 21..=64;
+
+/// The id the client asked for, or a fresh one when it asked for none or an invalid one
+pub fn trader_id(requested: Option<String>) -> TraderId {
+    match requested {
+        Some(requested)
+            if TRADER_ID_LENGTH.contains(&requested.len()) &&
+                requested.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') =>
+        {
+            TraderId(requested)
+        }
+        _ => TraderId(nanoid::nanoid!()),
+    }
+}
+
 pub async fn handle_connection(
     state: WebAppState,
     websocket: axum::extract::ws::WebSocket,
     market_id: u32,
+    trader_id: TraderId,
 ) {
     let (ws_sender, ws_receiver) = websocket.split();
     if let Some(market) = state.markets().get(&market_id) {
@@ -43,10 +65,11 @@ pub async fn handle_connection(
                 })
                 .map_err(|_| anyhow!("Axum WS error"))
             });
-            WsActor::add_stream(stream, ctx);
+            <WsActor as StreamHandler<Result<WsMsg, _>>>::add_stream(stream, ctx);
             WsActor {
-                trader_id: TraderId(nanoid::nanoid!()),
-                sender: Rc::new(Mutex::new(ws_sender)),
+                trader_id,
+                sender: Some(ws_sender),
+                outbox: Outbox::default(),
                 market,
                 hb: Instant::now(),
                 last_trader_time_ms: chrono::Utc::now().timestamp_millis() as u64,
@@ -62,7 +85,9 @@ struct WsMsg(pub Result<TraderMessage, anyhow::Error>);
 struct WsActor {
     trader_id: TraderId,
     market: Addr<MarketActor>,
-    sender: Rc<Mutex<SplitSink<WebSocket, ws::Message>>>,
+    /// `None` while a message is being written
+    sender: Option<SplitSink<WebSocket, ws::Message>>,
+    outbox: Outbox,
     last_trader_time_ms: u64,
     /// Client must send ping at least once per 10 seconds (CLIENT_TIMEOUT),
     /// otherwise we drop connection.
@@ -76,9 +101,24 @@ impl WsActor {
     /// How long before lack of client response causes a timeout
     const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
 
+    /// How long a client can keep falling behind on the messages sent to it before it gets
+    /// disconnected
+    const SLOW_CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// How long to wait for the close message to go out before dropping the connection
+    const CLOSE_TIMEOUT: Duration = Duration::from_secs(1);
+
     /// helper method that checks heartbeats from client
     fn hb(&self, ctx: &mut Context<Self>) {
         ctx.run_interval(Self::HEARTBEAT_INTERVAL, |act, ctx| {
+            // a client that is behind on its messages can't answer in time, whether it is still
+            // there is up to the slow client check
+            if act.outbox.behind_for().is_some() {
+                act.hb = Instant::now();
+                act.check_slow_client(ctx);
+                return
+            }
+
             // check client heartbeats
             if Instant::now().duration_since(act.hb) > Self::CLIENT_TIMEOUT {
                 // heartbeat timed out
@@ -92,25 +132,69 @@ impl WsActor {
         });
     }
 
-    fn send(&self, msg: ws::Message, ctx: &mut Context<Self>) {
-        let sender = self.sender.clone();
-        async move { sender.lock().await.send(msg).await }
-            .into_actor(self)
-            .map(|a, b, c| {
-                if a.is_err() {
-                    c.stop();
-                }
-                async {}.into_actor(b).spawn(c);
-            })
-            .spawn(ctx);
+    /// Write the next queued message, one at a time so that the queue is what grows when the
+    /// client is slow
+    fn flush(&mut self, ctx: &mut Context<Self>) {
+        if self.sender.is_none() {
+            return
+        }
+        if self.outbox.take_resync() {
+            self.market.do_send(RequestMarketState(self.trader_id.clone()));
+        }
+        let Some(msg) = self.outbox.pop() else {
+            return
+        };
+        let Some(mut sender) = self.sender.take() else {
+            return
+        };
+        let closing = matches!(msg, ws::Message::Close(_));
+        async move {
+            let res = sender.send(msg).await;
+            (sender, res)
+        }
+        .into_actor(self)
+        .map(move |(sender, res), act, ctx| {
+            if res.is_err() || closing {
+                ctx.stop();
+                return
+            }
+            act.sender = Some(sender);
+            act.flush(ctx);
+        })
+        .spawn(ctx);
     }
 
-    fn send_server_message(&self, msg: ServerMessage, ctx: &mut Context<Self>) {
+    fn send_server_message(&mut self, msg: ServerMessage, ctx: &mut Context<Self>) {
         let mut writer = Vec::new();
-        if let Ok(_) = ciborium::into_writer(&msg, &mut writer) {
-            let msg = ws::Message::Binary(writer);
-            self.send(msg, ctx);
+        if ciborium::into_writer(&msg, &mut writer).is_ok() {
+            self.outbox.push(ws::Message::Binary(writer));
+            self.check_slow_client(ctx);
+            self.flush(ctx);
+        }
+    }
+
+    /// Queue market data, which gets dropped and later replaced by the whole market state if the
+    /// client can't keep up
+    fn send_market_data(&mut self, msg: ws::Message, ctx: &mut Context<Self>) {
+        self.outbox.push_market_data(msg);
+        self.check_slow_client(ctx);
+        self.flush(ctx);
+    }
+
+    /// Disconnect the client if it has been falling behind for too long
+    fn check_slow_client(&mut self, ctx: &mut Context<Self>) {
+        let Some(behind_for) = self.outbox.behind_for() else {
+            return
+        };
+        if behind_for < Self::SLOW_CLIENT_TIMEOUT {
+            return
         }
+        tracing::warn!(agent =? self.trader_id, "Websocket client too slow, disconnecting");
+        self.outbox.close(ws::Message::Close(Some(CloseFrame {
+            code: ws::close_code::POLICY,
+            reason: "Too slow to keep up with the market".into(),
+        })));
+        ctx.run_later(Self::CLOSE_TIMEOUT, |_act, ctx| ctx.stop());
     }
 }
 
@@ -122,15 +206,34 @@ impl Actor for WsActor {
         // register client to the market
         let recp = ctx.address().recipient();
         let recp2 = ctx.address().recipient();
-        self.market.do_send(RegisterTrader(self.trader_id.clone(), recp, recp2));
+        self.market
+            .send(RegisterTrader(self.trader_id.clone(), recp, recp2))
+            .into_actor(self)
+            .map(|res, act, ctx| match res {
+                Ok(Ok(market_data)) => {
+                    ctx.add_stream(market_data.into_stream());
+                }
+                Ok(Err(AlreadyConnected)) => {
+                    act.outbox.close(ws::Message::Close(Some(CloseFrame {
+                        code: TRADER_ALREADY_CONNECTED,
+                        reason: "Trader is already connected".into(),
+                    })));
+                    act.flush(ctx);
+                    ctx.run_later(Self::CLOSE_TIMEOUT, |_act, ctx| ctx.stop());
+                }
+                Err(_) => ctx.stop(),
+            })
+            .spawn(ctx);
         self.send_server_message(ServerMessage::TraderTimeAck, ctx);
 
         // we'll start heartbeat process on session start.
         self.hb(ctx);
     }
 
-    fn stopped(&mut self, _ctx: &mut Self::Context) {
+    fn stopped(&mut self, ctx: &mut Self::Context) {
         tracing::warn!(agent =? self.trader_id, "ws actor stopped");
+        let recp = ctx.address().recipient();
+        self.market.do_send(DeregisterTrader(self.trader_id.clone(), recp));
     }
 }
 
@@ -139,12 +242,45 @@ impl StreamHandler<Result<WsMsg, anyhow::Error>> for WsActor {
         if let Ok(WsMsg(Ok(msg))) = item {
             match msg {
                 TraderMessage::PlaceOrder(req_id, order) => {
-                    self.market.do_send(trading_logic::market::messages::PlaceOrder {
-                        request_id: req_id,
+                    let msg = PlaceOrder {
+                        request_id: req_id.clone(),
                         trader: self.trader_id.clone(),
                         order,
+                    };
+                    self.submit_order(req_id, msg, ctx);
+                }
+                TraderMessage::CashOut(selection) => {
+                    let req_id = RequestId(nanoid::nanoid!());
+                    let msg = CashOut {
+                        request_id: req_id.clone(),
+                        trader: self.trader_id.clone(),
+                        selection,
+                    };
+                    self.submit_order(req_id, msg, ctx);
+                }
+                TraderMessage::CancelOrder(req_id) => {
+                    self.market.do_send(CancelOrder {
+                        request_id: req_id,
+                        trader: self.trader_id.clone(),
+                    });
+                }
+                TraderMessage::CancelAllAtTick(selection, tick) => {
+                    self.market.do_send(CancelOrders {
+                        selection: Some(selection),
+                        tick: Some(tick),
+                        trader: self.trader_id.clone(),
                     });
                 }
+                TraderMessage::CancelAll => {
+                    self.market.do_send(CancelOrders {
+                        selection: None,
+                        tick: None,
+                        trader: self.trader_id.clone(),
+                    });
+                }
+                TraderMessage::RequestSnapshot => {
+                    self.market.do_send(RequestMarketState(self.trader_id.clone()));
+                }
                 TraderMessage::TraderTime { ms: time } => {
                     self.last_trader_time_ms = time;
                     self.send_server_message(ServerMessage::TraderTimeAck, ctx)
@@ -165,25 +301,83 @@ impl Handler<TickDataUpdate> for WsActor {
 
     fn handle(&mut self, msg: TickDataUpdate, ctx: &mut Context<Self>) -> Self::Result {
         tracing::info!(msg = ?msg, "TickDataUpdate");
+        self.send_server_message(ServerMessage::from(msg), ctx);
+    }
+}
+
+/// Market data shared with every other connection to the market, already encoded
+impl StreamHandler<Result<Arc<MarketData>, u64>> for WsActor {
+    fn handle(&mut self, item: Result<Arc<MarketData>, u64>, ctx: &mut Context<Self>) {
+        match item {
+            Ok(data) => {
+                tracing::debug!(msg = ?data.update, "Market data");
+                // axum 0.6 only takes an owned `Vec<u8>`, so this is one memcpy per connection
+                self.send_market_data(ws::Message::Binary(data.encoded.clone()), ctx);
+            }
+            Err(missed) => {
+                tracing::warn!(agent =? self.trader_id, missed, "Market data lagged, resending");
+                self.outbox.drop_market_data();
+                self.check_slow_client(ctx);
+                self.flush(ctx);
+            }
+        }
+    }
+}
+
+impl Handler<TraderUpdate> for WsActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: TraderUpdate, ctx: &mut Context<Self>) -> Self::Result {
+        tracing::info!(msg = ?msg, "TraderUpdate");
         let msg = match msg {
-            TickDataUpdate::SetRefresh(msg) => ServerMessage::TickSetWhole(msg),
-            TickDataUpdate::SingleUpdate(msg) => ServerMessage::TickUpdate(msg),
-            TickDataUpdate::NewLatestMatch(msg) => ServerMessage::NewLatestMatch(msg),
+            TraderUpdate::OrderState(msg) => ServerMessage::OrderStateUpdate(msg),
+            TraderUpdate::OrderFilled(fill) => order_filled_message(fill),
+            TraderUpdate::SelfTradePrevented(self_trade) => {
+                ServerMessage::SelfTradePrevented(self_trade)
+            }
+            TraderUpdate::Settlement(settlement) => ServerMessage::Settlement(settlement),
+            TraderUpdate::TraderInfo(info) => ServerMessage::TraderInfo(info),
+            TraderUpdate::Positions(positions) => ServerMessage::Positions(positions),
         };
         self.send_server_message(msg, ctx);
     }
 }
-impl Handler<OrderStateUpdate> for WsActor {
-    type Result = ();
 
-    fn handle(&mut self, msg: OrderStateUpdate, ctx: &mut Context<Self>) -> Self::Result {
-        tracing::info!(msg = ?msg, "OrderStateUpdate");
-        self.send_server_message(
-            ServerMessage::OrderStateUpdate(trading_types::from_server::TraderOrders {
-                matched_orders: msg.matched_orders,
-                unmatched_orders: msg.open_orders,
-            }),
-            ctx,
-        );
+impl WsActor {
+    /// Forward an order to the market and acknowledge it to the trader once it got processed
+    fn submit_order<M>(&mut self, request_id: RequestId, msg: M, ctx: &mut Context<Self>)
+    where
+        M: actix::Message<Result = Result<OrderPlaced, RejectReason>> + Send + 'static,
+        MarketActor: Handler<M>,
+    {
+        self.market
+            .send(msg)
+            .into_actor(self)
+            .map(move |res, act, ctx| {
+                let res = res.unwrap_or(Err(RejectReason::MarketUnavailable));
+                let placed = match res {
+                    Ok(placed) => placed,
+                    Err(reason) => {
+                        let msg = ServerMessage::OrderRejected { request_id, reason };
+                        act.send_server_message(msg, ctx);
+                        return
+                    }
+                };
+
+                act.send_server_message(ServerMessage::OrderAccepted(request_id), ctx);
+                for fill in placed.fills {
+                    act.send_server_message(order_filled_message(fill), ctx);
+                }
+            })
+            .spawn(ctx);
+    }
+}
+
+fn order_filled_message(fill: OrderFill) -> ServerMessage {
+    ServerMessage::OrderFilled {
+        request_id: fill.request_id,
+        price: fill.price,
+        matched: fill.matched,
+        remaining: fill.remaining,
     }
 }
@@ -0,0 +1,26 @@
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::Json;
+use serde::Deserialize;
+use state::WebAppState;
+use trading_logic::market::messages::GetCandles;
+use trading_types::from_server::{Candle, CandleInterval};
+
+#[derive(Debug, Deserialize)]
+pub struct CandlesQuery {
+    interval: CandleInterval,
+}
+
+/// Recent candles of every selection of the market
+pub async fn handler(
+    Path(market_id): Path<u32>,
+    Query(query): Query<CandlesQuery>,
+    State(state): State<WebAppState>,
+) -> Result<Json<Vec<Candle>>, StatusCode> {
+    let market = state.markets().get(&market_id).ok_or(StatusCode::NOT_FOUND)?;
+    let candles = market
+        .send(GetCandles(query.interval))
+        .await
+        .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?;
+    Ok(Json(candles))
+}
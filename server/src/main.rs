@@ -1,7 +1,7 @@
 use std::sync::Arc;
 
 use app::*;
-use axum::routing::{any, get};
+use axum::routing::{any, get, post};
 use axum::{Extension, Router};
 use fileserv::file_and_error_handler;
 use leptos::leptos_server::server_fns_by_path;
@@ -9,7 +9,9 @@ use leptos::*;
 use leptos_axum::{generate_route_list, LeptosRoutes};
 use tracing_subscriber::prelude::*;
 
+pub mod candles;
 pub mod fileserv;
+pub mod settle;
 
 #[tokio::main]
 async fn main() {
@@ -30,9 +32,12 @@ async fn main() {
     tracing::info!(server_fns =? server_fns, "Registered Leptos server functions");
 
     let (state, handle) = state::spawn_actix_rt(leptos_options.clone());
+    let shutdown_state = state.clone();
 
     let app = Router::new()
         .route("/ws/:id", get(live_connection::handler))
+        .route("/candles/:id", get(candles::handler))
+        .route("/settle/:id", post(settle::handler))
         .with_state(state)
         .route("/api/*fn_name", any(leptos_axum::handle_server_fns))
         .leptos_routes(leptos_options.clone(), routes, |cx| view! { cx, <App/> })
@@ -43,11 +48,44 @@ async fn main() {
     // `axum::Server` is a re-export of `hyper::Server`
     tracing::info!("listening on http://{}", &addr);
 
-    let server = axum::Server::bind(&addr).serve(app.into_make_service());
+    let server = axum::Server::bind(&addr)
+        .serve(app.into_make_service())
+        .with_graceful_shutdown(shutdown_signal());
     let _ = server.await;
+    shutdown_state.shutdown().await;
     handle.join().unwrap().unwrap();
 }
 
+/// Resolves once the server is asked to stop with Ctrl+C or SIGTERM
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(err) = tokio::signal::ctrl_c().await {
+            tracing::error!(err = ?err, "Failed to listen for Ctrl+C");
+            std::future::pending::<()>().await
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(err) => {
+                tracing::error!(err = ?err, "Failed to listen for SIGTERM");
+                std::future::pending::<()>().await
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+    tracing::info!("Shutting down");
+}
+
 fn init_tracing() {
     // construct a subscriber that prints formatted traces to stdout
     // use that subscriber to process traces emitted after this point
@@ -0,0 +1,64 @@
+use axum::extract::{Path, State};
+use axum::headers::authorization::Bearer;
+use axum::headers::Authorization;
+use axum::http::StatusCode;
+use axum::{Json, TypedHeader};
+use serde::Deserialize;
+use state::WebAppState;
+use trading_logic::market::messages::{SetMarketStatus, SettleMarket};
+use trading_types::common::{MarketStatus, SelectionId};
+
+/// Environment variable with the token operators authenticate with, settling is disabled without
+/// it
+const OPERATOR_TOKEN: &str = "OPERATOR_TOKEN";
+
+#[derive(Debug, Deserialize)]
+pub struct SettleRequest {
+    winner: SelectionId,
+}
+
+/// Close the market, settle it against the declared winner and open it again for the next round
+pub async fn handler(
+    Path(market_id): Path<u32>,
+    State(state): State<WebAppState>,
+    auth: Option<TypedHeader<Authorization<Bearer>>>,
+    Json(request): Json<SettleRequest>,
+) -> Result<StatusCode, StatusCode> {
+    let token = std::env::var(OPERATOR_TOKEN).map_err(|_| StatusCode::FORBIDDEN)?;
+    match auth {
+        Some(TypedHeader(auth)) if !token.is_empty() && auth.token() == token => {}
+        _ => return Err(StatusCode::UNAUTHORIZED),
+    }
+
+    let market = state::get_markets()
+        .into_iter()
+        .find(|market| market.id == market_id)
+        .ok_or(StatusCode::NOT_FOUND)?;
+    if !(1..=market.selections.len() as u32).contains(&request.winner.0) {
+        return Err(StatusCode::UNPROCESSABLE_ENTITY)
+    }
+    let market = state.markets().get(&market_id).ok_or(StatusCode::NOT_FOUND)?;
+
+    tracing::info!(market_id, winner = ?request.winner, "Operator is settling the market");
+    let send_status = |status| async move {
+        market.send(SetMarketStatus(status)).await.map_err(|_| StatusCode::SERVICE_UNAVAILABLE)
+    };
+    // A market that is already closed only needs settling
+    match send_status(MarketStatus::Closed).await? {
+        Ok(()) => {}
+        Err(err) if err.from == MarketStatus::Closed => {}
+        Err(_) => return Err(StatusCode::CONFLICT),
+    }
+    market
+        .send(SettleMarket(request.winner))
+        .await
+        .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?
+        .map_err(|_| StatusCode::CONFLICT)?;
+
+    for status in [MarketStatus::Inactive, MarketStatus::Open] {
+        if let Err(err) = send_status(status).await? {
+            tracing::warn!(err = ?err, market_id, "Failed to reopen the settled market");
+        }
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -1,11 +1,26 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
 use serde::{Deserialize, Serialize};
+use trading_types::common::TraderId;
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Market {
     pub id: u32,
     pub name: String,
     pub event: String,
+    /// Names of the runners, each traded on its own ladder
+    pub selections: Vec<String>,
     pub bots: u32,
+    /// When set, the market is settled with a random winner this often, otherwise it waits for an
+    /// operator to declare the winner
+    pub round_duration: Option<Duration>,
+    /// Share of the net winnings kept from every trader when the market settles
+    pub commission_rate: Decimal,
+    /// Fraction of the commission that is waived for these traders
+    pub commission_discounts: BTreeMap<TraderId, Decimal>,
 }
 
 pub fn get_markets() -> Vec<Market> {
@@ -13,19 +28,31 @@ pub fn get_markets() -> Vec<Market> {
         Market {
             id: 1,
             name: "Mouz vs ENCE".to_string(),
+            selections: vec!["Mouz".to_string(), "ENCE".to_string()],
             bots: 5,
+            round_duration: Some(Duration::from_secs(60)),
+            commission_rate: dec!(0.05),
+            commission_discounts: BTreeMap::new(),
             event: "BLAST.TV Major".to_string(),
         },
         Market {
             id: 2,
             name: "G2 vs FaZe".to_string(),
+            selections: vec!["G2".to_string(), "FaZe".to_string()],
             bots: 0,
+            round_duration: None,
+            commission_rate: dec!(0.05),
+            commission_discounts: BTreeMap::new(),
             event: "BLAST.TV Major".to_string(),
         },
         Market {
             id: 3,
             name: "Liquid vs Astralis".to_string(),
+            selections: vec!["Liquid".to_string(), "Astralis".to_string()],
             bots: 0,
+            round_duration: None,
+            commission_rate: dec!(0.05),
+            commission_discounts: BTreeMap::new(),
             event: "BLAST.TV Major".to_string(),
         },
     ]
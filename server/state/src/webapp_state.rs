@@ -1,12 +1,24 @@
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use actix::*;
 use axum::extract::FromRef;
 use leptos::LeptosOptions;
-use trading_logic::market::messages::SpawnBot;
-use trading_logic::market::MarketActor;
+use trading_logic::journal::Journal;
+use trading_logic::market::messages::{SaveSnapshot, SpawnBot};
+use trading_logic::market::{MarketActor, MarketConfig};
+use trading_logic::settlement::Commission;
+use trading_logic::snapshot::MarketSnapshot;
+use trading_types::common::{Selection, SelectionId};
 
-use crate::get_markets;
+use crate::{get_markets, Market};
+
+/// Directory where every market appends its journal
+const JOURNAL_DIR: &str = "journal";
+/// Directory with the latest snapshot of every market
+const SNAPSHOT_DIR: &str = "snapshots";
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(10);
 
 #[derive(FromRef, Debug, Clone)]
 pub struct WebAppState {
@@ -19,7 +31,7 @@ impl WebAppState {
     pub fn new(arb: ArbiterHandle, leptos_options: LeptosOptions) -> Self {
         let mut markets = HashMap::new();
         for market in get_markets() {
-            let market_actor = Self::spawn_market(&arb);
+            let market_actor = Self::spawn_market(&arb, &market);
             for _ in 0..market.bots {
                 market_actor.do_send(SpawnBot);
             }
@@ -32,11 +44,124 @@ impl WebAppState {
         &self.arb
     }
 
-    fn spawn_market(arb: &ArbiterHandle) -> Addr<MarketActor> {
-        MarketActor::start_in_arbiter(arb, move |_ctx| MarketActor::new())
+    fn spawn_market(arb: &ArbiterHandle, market: &Market) -> Addr<MarketActor> {
+        let selections = market
+            .selections
+            .iter()
+            .zip(1..)
+            .map(|(name, id)| Selection { id: SelectionId(id), name: name.clone() })
+            .collect();
+        let config = MarketConfig {
+            selections,
+            round_duration: market.round_duration,
+            commission: Commission {
+                rate: market.commission_rate,
+                discounts: market.commission_discounts.clone().into_iter().collect(),
+            },
+            ..MarketConfig::default()
+        };
+        let snapshot_path = Self::snapshot_path(market.id);
+        let snapshot = snapshot_path
+            .as_ref()
+            .and_then(|path| Self::read_snapshot(market.id, path))
+            .and_then(|snapshot| Self::with_config(market.id, snapshot, &config));
+        let journal = Self::open_journal(market.id);
+        MarketActor::start_in_arbiter(arb, move |_ctx| {
+            let mut market = match snapshot {
+                Some(snapshot) => MarketActor::restore(snapshot),
+                None => MarketActor::new(config),
+            };
+            if let Some(path) = snapshot_path {
+                market = market.with_snapshots(path, SNAPSHOT_INTERVAL);
+            }
+            match journal {
+                Some(journal) => market.with_journal(journal),
+                None => market,
+            }
+        })
+    }
+
+    /// Where the market keeps its snapshot, `None` if the directory can't be created
+    fn snapshot_path(market_id: u32) -> Option<PathBuf> {
+        let dir = Path::new(SNAPSHOT_DIR);
+        match std::fs::create_dir_all(dir) {
+            Ok(()) => Some(dir.join(format!("market-{market_id}.cbor"))),
+            Err(err) => {
+                tracing::error!(err = ?err, market_id, "Failed to create the snapshot directory");
+                None
+            }
+        }
+    }
+
+    /// The snapshot of the previous run, a market without one starts empty
+    fn read_snapshot(market_id: u32, path: &Path) -> Option<MarketSnapshot> {
+        if !path.exists() {
+            return None
+        }
+        match MarketSnapshot::read(path) {
+            Ok(snapshot) => {
+                tracing::info!(market_id, "Restoring market from its snapshot");
+                Some(snapshot)
+            }
+            Err(err) => {
+                tracing::error!(err = ?err, market_id, "Failed to read the market snapshot");
+                None
+            }
+        }
+    }
+
+    /// The snapshot running with the market's current config, `None` if it was taken of a market
+    /// with other selections
+    fn with_config(
+        market_id: u32,
+        mut snapshot: MarketSnapshot,
+        config: &MarketConfig,
+    ) -> Option<MarketSnapshot> {
+        let ids = |selections: &[Selection]| {
+            selections.iter().map(|selection| selection.id).collect::<Vec<_>>()
+        };
+        if ids(&snapshot.config.selections) != ids(&config.selections) {
+            tracing::error!(
+                market_id,
+                snapshot = ?snapshot.config.selections,
+                config = ?config.selections,
+                "Snapshot is of other selections, starting the market empty"
+            );
+            return None
+        }
+        snapshot.config = config.clone();
+        Some(snapshot)
+    }
+
+    /// Journal of the market, markets keep running without one if it can't be opened
+    fn open_journal(market_id: u32) -> Option<Journal> {
+        let dir = Path::new(JOURNAL_DIR);
+        let journal = std::fs::create_dir_all(dir)
+            .and_then(|()| Journal::open(dir.join(format!("market-{market_id}.cbor"))));
+        match journal {
+            Ok(journal) => Some(journal),
+            Err(err) => {
+                tracing::error!(err = ?err, market_id, "Failed to open the market journal");
+                None
+            }
+        }
     }
 
     pub fn markets(&self) -> &HashMap<u32, Addr<MarketActor>> {
         &self.markets
     }
+
+    /// Save a final snapshot of every market and stop the actix runtime
+    pub async fn shutdown(&self) {
+        for (market_id, market) in self.markets.iter() {
+            match market.send(SaveSnapshot).await {
+                Ok(Ok(())) => tracing::info!(market_id, "Saved market snapshot"),
+                Ok(Err(err)) => {
+                    tracing::error!(err = ?err, market_id, "Failed to save the market snapshot")
+                }
+                Err(err) => tracing::error!(err = ?err, market_id, "Market is unavailable"),
+            }
+        }
+        self.arb.spawn(async { System::current().stop() });
+    }
 }
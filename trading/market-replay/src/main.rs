@@ -0,0 +1,98 @@
+//! Rebuilds a market from its journal and checks that every command produces the same events
+//! again.
+//!
+//! Usage: `market-replay journal/market-1.cbor`
+
+use chrono::{DateTime, Utc};
+use trading_logic::engine::{Command, Event, MatchingEngine};
+use trading_logic::journal::{Journal, JournalEntry};
+use trading_logic::snapshot::MarketSnapshot;
+
+/// A started market and everything that got journaled for it
+struct Segment {
+    start: MarketSnapshot,
+    commands: Vec<(JournaledCommand, Vec<Event>)>,
+}
+
+type JournaledCommand = (DateTime<Utc>, Command);
+
+fn segments(entries: Vec<JournalEntry>) -> anyhow::Result<Vec<Segment>> {
+    let mut segments = Vec::<Segment>::new();
+    for entry in entries {
+        match entry {
+            JournalEntry::Started(start) => {
+                segments.push(Segment { start: *start, commands: vec![] })
+            }
+            JournalEntry::Command { at, command } => segments
+                .last_mut()
+                .ok_or_else(|| anyhow::anyhow!("Command before the market got started"))?
+                .commands
+                .push(((at, command), vec![])),
+            JournalEntry::Event(event) => segments
+                .last_mut()
+                .and_then(|segment| segment.commands.last_mut())
+                .ok_or_else(|| anyhow::anyhow!("Event without a command"))?
+                .1
+                .push(event),
+        }
+    }
+    Ok(segments)
+}
+
+fn main() -> anyhow::Result<()> {
+    let path = std::env::args().nth(1).ok_or_else(|| anyhow::anyhow!("Missing journal path"))?;
+    let segments = segments(Journal::read(path)?)?;
+
+    let mut mismatches = 0;
+    for (idx, segment) in segments.into_iter().enumerate() {
+        println!("==== Market run {idx} ====");
+        let mut engine = MatchingEngine::restore(segment.start);
+        for ((at, command), expected) in segment.commands {
+            let (_outcome, actual) = engine.execute(command.clone(), at);
+            if actual != expected {
+                mismatches += 1;
+                println!("Mismatch for {command:?}");
+                println!("  journaled: {expected:?}");
+                println!("  replayed:  {actual:?}");
+            }
+        }
+
+        println!("-- Order book --");
+        for tick_data in engine.snapshot().ticks {
+            if tick_data.available_backs.0.is_zero() && tick_data.available_lays.0.is_zero() {
+                continue
+            }
+            println!(
+                "{:?} @ {}: backs {} lays {} matched {}",
+                tick_data.selection,
+                tick_data.tick.0,
+                tick_data.available_backs.0,
+                tick_data.available_lays.0,
+                tick_data.total_matched.0
+            );
+        }
+        println!("-- Traders --");
+        for (trader, account) in engine.traders() {
+            println!("{}: balance {}", trader.0, account.balance.0);
+            let mut orders = account.orders.values().collect::<Vec<_>>();
+            orders.sort_by(|a, b| a.request_id.cmp(&b.request_id));
+            for order in orders {
+                println!(
+                    "  {} {:?} {} @ {}: matched {} remaining {}",
+                    order.request_id.0,
+                    order.selection,
+                    order.side,
+                    order.tick.0,
+                    order.matched_size.0,
+                    order.remaining_size.0
+                );
+            }
+        }
+    }
+
+    if mismatches > 0 {
+        anyhow::bail!("{mismatches} commands replayed differently");
+    }
+    println!("Replay matches the journal");
+    Ok(())
+}
@@ -0,0 +1,176 @@
+//! Order throughput of the matching engine under a load similar to the market bots.
+//!
+//! Run with `cargo bench -p trading-logic`.
+
+use chrono::Utc;
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use trading_logic::engine::{Command, MarketConfig, MatchingEngine};
+use trading_types::common::{
+    MarketStatus, Order, RequestId, SelectionId, Side, Size, Tick, TraderId,
+};
+
+const TRADERS: usize = 20;
+
+fn trader_id(trader: usize) -> TraderId {
+    TraderId(format!("bot-{trader}"))
+}
+
+/// An open market with every trader registered
+fn engine() -> MatchingEngine {
+    let config = MarketConfig {
+        round_duration: None,
+        starting_balance: Size(dec!(1000000000)),
+        ..MarketConfig::default()
+    };
+    let mut engine = MatchingEngine::new(config);
+    let now = Utc::now();
+    for trader in 0..TRADERS {
+        engine.execute(Command::RegisterTrader { trader: trader_id(trader) }, now);
+    }
+    engine.execute(Command::SetStatus(MarketStatus::Open), now);
+    engine
+}
+
+/// Orders around a slowly drifting price like the bots place them: backs up to two ticks below
+/// it, lays up to two ticks above it. Every `cancel_every`th command cancels an earlier order.
+fn bot_load(orders: usize, cancel_every: Option<usize>) -> Vec<Command> {
+    let mut rng = StdRng::seed_from_u64(42);
+    let selections = MarketConfig::default().selections;
+    let mut prices = vec![Tick(dec!(1.50)).index().unwrap(); selections.len()];
+    let mut placed = vec![];
+    let mut commands = Vec::with_capacity(orders);
+
+    for idx in 0..orders {
+        if let Some((trader, request_id)) = cancel_every
+            .filter(|cancel_every| idx % cancel_every == 0 && !placed.is_empty())
+            .map(|_| placed.swap_remove(rng.gen_range(0..placed.len())))
+        {
+            commands.push(Command::CancelOrder { trader, request_id });
+            continue
+        }
+
+        let selection = rng.gen_range(0..selections.len());
+        let price = &mut prices[selection];
+        *price = price.saturating_add_signed(rng.gen_range(-1..=1)).clamp(10, 300);
+        let side = if rng.gen_bool(0.5) { Side::Back } else { Side::Lay };
+        let offset = rng.gen_range(0..=2);
+        let tick_index = match side {
+            Side::Back => *price - offset,
+            Side::Lay => *price + offset,
+        };
+
+        let trader = trader_id(rng.gen_range(0..TRADERS));
+        let request_id = RequestId(format!("request-{idx}"));
+        placed.push((trader.clone(), request_id.clone()));
+        commands.push(Command::PlaceOrder {
+            trader,
+            request_id,
+            order: Order {
+                selection: selections[selection].id,
+                tick: Tick::from_index(tick_index).unwrap(),
+                size: Size(Decimal::new(rng.gen_range(2..300), 0)),
+                side,
+            },
+        });
+    }
+    commands
+}
+
+/// Lays resting on every tick of the ladder and large backs at 1.01 sweeping them from the top
+fn deep_book_sweep(levels: usize, sweeps: usize) -> (Vec<Command>, Vec<Command>) {
+    let selection = SelectionId(1);
+    let resting = (0..levels)
+        .map(|idx| Command::PlaceOrder {
+            trader: trader_id(idx % TRADERS),
+            request_id: RequestId(format!("resting-{idx}")),
+            order: Order {
+                selection,
+                tick: Tick::from_index(idx).unwrap(),
+                size: Size(dec!(100)),
+                side: Side::Lay,
+            },
+        })
+        .collect();
+    let sweeping = (0..sweeps)
+        .map(|idx| Command::PlaceOrder {
+            trader: trader_id(idx % TRADERS),
+            request_id: RequestId(format!("sweep-{idx}")),
+            order: Order { selection, tick: Tick::MIN, size: Size(dec!(1000)), side: Side::Back },
+        })
+        .collect();
+    (resting, sweeping)
+}
+
+fn run(engine: &mut MatchingEngine, commands: Vec<Command>) {
+    let now = Utc::now();
+    for command in commands {
+        engine.execute(command, now);
+    }
+}
+
+fn bench_bot_load(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bot_load");
+    for orders in [1_000, 10_000] {
+        group.throughput(Throughput::Elements(orders as u64));
+        for (name, cancel_every) in [("place", None), ("place_and_cancel", Some(4))] {
+            let commands = bot_load(orders, cancel_every);
+            group.bench_with_input(BenchmarkId::new(name, orders), &commands, |b, commands| {
+                b.iter_batched(
+                    || (engine(), commands.clone()),
+                    |(mut engine, commands)| run(&mut engine, commands),
+                    BatchSize::LargeInput,
+                )
+            });
+        }
+    }
+    group.finish();
+}
+
+fn bench_deep_book(c: &mut Criterion) {
+    let mut group = c.benchmark_group("deep_book");
+    let (resting, sweeping) = deep_book_sweep(Tick::all().len(), 20);
+    group.throughput(Throughput::Elements(sweeping.len() as u64));
+    group.bench_function("sweep", |b| {
+        b.iter_batched(
+            || {
+                let mut engine = engine();
+                run(&mut engine, resting.clone());
+                (engine, sweeping.clone())
+            },
+            |(mut engine, commands)| run(&mut engine, commands),
+            BatchSize::LargeInput,
+        )
+    });
+
+    // Every resting order cancelled one by one, each cancel looking for a single order
+    let cancelling = resting
+        .iter()
+        .filter_map(|command| match command {
+            Command::PlaceOrder { trader, request_id, .. } => Some(Command::CancelOrder {
+                trader: trader.clone(),
+                request_id: request_id.clone(),
+            }),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    group.throughput(Throughput::Elements(cancelling.len() as u64));
+    group.bench_function("cancel", |b| {
+        b.iter_batched(
+            || {
+                let mut engine = engine();
+                run(&mut engine, resting.clone());
+                (engine, cancelling.clone())
+            },
+            |(mut engine, commands)| run(&mut engine, commands),
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_bot_load, bench_deep_book);
+criterion_main!(benches);
@@ -1,17 +1,24 @@
+use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
-use actix::{Actor, Addr, AsyncContext, Context, Handler, Message};
+use actix::{
+    Actor, ActorContext, ActorFutureExt, Addr, AsyncContext, Context, ContextFutureSpawner,
+    Handler, Message, StreamHandler, WrapFuture,
+};
 use rand::Rng;
 use rust_decimal_macros::dec;
-use trading_types::common::{Order, RequestId, Side, Size, Tick, TraderId};
+use trading_types::common::{Order, RequestId, SelectionId, Side, Size, Tick, TraderId};
 
-use crate::market::messages::{OrderStateUpdate, PlaceOrder, TickDataUpdate};
-use crate::market::MarketActor;
+use crate::market::messages::{PlaceOrder, TickDataUpdate, TraderUpdate};
+use crate::market::{MarketActor, MarketData};
 
 pub struct BotActor {
     trader_id: TraderId,
     market: Addr<MarketActor>,
     next_placement_order: Order,
+    /// Selections of the market together with the tick they last matched at
+    latest_matches: HashMap<SelectionId, Tick>,
     random: rand::rngs::ThreadRng,
     spawn_handle: Option<actix::SpawnHandle>,
     last_placed_bet_time: std::time::Instant,
@@ -23,16 +30,18 @@ impl BotActor {
         let mut instance = Self {
             trader_id,
             next_placement_order: Order {
+                selection: SelectionId(1),
                 size: Size(dec!(2.0)),
                 side: Side::Back,
                 tick: Tick(dec!(1.50)),
             },
+            latest_matches: HashMap::new(),
             spawn_handle: None,
             market,
             random,
             last_placed_bet_time: Instant::now(),
         };
-        instance.roll_new_order(instance.next_placement_order.tick);
+        instance.roll_new_order();
         instance
     }
 }
@@ -44,11 +53,20 @@ impl Actor for BotActor {
     type Context = Context<Self>;
 
     fn started(&mut self, ctx: &mut Self::Context) {
-        self.market.do_send(crate::market::messages::RegisterTrader(
-            self.trader_id.clone(),
-            ctx.address().recipient(),
-            ctx.address().recipient(),
-        ));
+        self.market
+            .send(crate::market::messages::RegisterTrader(
+                self.trader_id.clone(),
+                ctx.address().recipient(),
+                ctx.address().recipient(),
+            ))
+            .into_actor(self)
+            .map(|res, _act, ctx| match res {
+                Ok(Ok(market_data)) => {
+                    ctx.add_stream(market_data.into_stream());
+                }
+                Ok(Err(_)) | Err(_) => ctx.stop(),
+            })
+            .spawn(ctx);
 
         let next_placement_in = Duration::from_millis(self.random.gen_range(500..2000));
         ctx.notify_later(PlaceNextBet, next_placement_in);
@@ -64,19 +82,36 @@ impl Handler<TickDataUpdate> for BotActor {
 
     fn handle(&mut self, msg: TickDataUpdate, ctx: &mut Context<Self>) -> Self::Result {
         match msg {
-            TickDataUpdate::NewLatestMatch(msg) => {
-                if self.random.gen_bool(0.5) {
-                    self.roll_new_order(msg.tick);
-                }
+            TickDataUpdate::Selections(selections) => {
+                self.latest_matches = selections
+                    .into_iter()
+                    .map(|selection| (selection.id, Tick(dec!(1.50))))
+                    .collect();
+                self.roll_new_order();
             }
             TickDataUpdate::SetRefresh(_msg) => {
                 if let Some(spawn_handle) = self.spawn_handle.take() {
                     ctx.cancel_future(spawn_handle);
-                    self.roll_new_order(Tick(dec!(1.50)));
+                    for tick in self.latest_matches.values_mut() {
+                        *tick = Tick(dec!(1.50));
+                    }
+                    self.roll_new_order();
                 }
             }
-            TickDataUpdate::SingleUpdate(msg) => {
-                if self.random.gen_bool(0.05) {
+            TickDataUpdate::MarketStatus(_status) => {}
+            TickDataUpdate::Trade(_trade) => {}
+            TickDataUpdate::TradeHistory(_trades) => {}
+            TickDataUpdate::LadderDelta(delta) => {
+                for msg in delta.latest_matches {
+                    self.latest_matches.insert(msg.selection, msg.tick);
+                    if self.random.gen_bool(0.5) {
+                        self.roll_new_order();
+                    }
+                }
+                for msg in delta.ticks {
+                    if !self.random.gen_bool(0.05) {
+                        continue
+                    }
                     let (side, size) = if msg.available_backs.0 > msg.available_lays.0 {
                         let half_size = msg.available_lays.0 / dec!(2.0);
                         (Side::Back, Size(half_size))
@@ -88,7 +123,7 @@ impl Handler<TickDataUpdate> for BotActor {
                     let msg = PlaceOrder {
                         request_id: RequestId(nanoid::nanoid!()),
                         trader: self.trader_id.clone(),
-                        order: Order { side, size, tick: msg.tick },
+                        order: Order { selection: msg.selection, side, size, tick: msg.tick },
                     };
                     self.market.do_send(msg);
                 }
@@ -96,10 +131,19 @@ impl Handler<TickDataUpdate> for BotActor {
         };
     }
 }
-impl Handler<OrderStateUpdate> for BotActor {
+impl StreamHandler<Result<Arc<MarketData>, u64>> for BotActor {
+    fn handle(&mut self, item: Result<Arc<MarketData>, u64>, ctx: &mut Context<Self>) {
+        // Missed updates don't matter, the next ones are just as good to trade on
+        if let Ok(data) = item {
+            Handler::handle(self, data.update.clone(), ctx);
+        }
+    }
+}
+
+impl Handler<TraderUpdate> for BotActor {
     type Result = ();
 
-    fn handle(&mut self, _msg: OrderStateUpdate, _ctx: &mut Context<Self>) -> Self::Result {
+    fn handle(&mut self, _msg: TraderUpdate, _ctx: &mut Context<Self>) -> Self::Result {
         // noop
     }
 }
@@ -120,7 +164,7 @@ impl Handler<PlaceNextBet> for BotActor {
             order: self.next_placement_order.clone(),
         };
         self.market.do_send(msg);
-        self.roll_new_order(self.next_placement_order.tick);
+        self.roll_new_order();
 
         // Schedule next placement
         let next_placement_in = Duration::from_millis(self.random.gen_range(500..2000));
@@ -129,13 +173,22 @@ impl Handler<PlaceNextBet> for BotActor {
 }
 
 impl BotActor {
-    fn roll_new_order(&mut self, prev_balance: Tick) {
+    fn roll_new_order(&mut self) {
+        let selections = self.latest_matches.keys().copied().collect::<Vec<_>>();
+        let next_placement_selection = if selections.is_empty() {
+            self.next_placement_order.selection
+        } else {
+            selections[self.random.gen_range(0..selections.len())]
+        };
+        let prev_balance =
+            self.latest_matches.get(&next_placement_selection).copied().unwrap_or(Tick(dec!(1.50)));
         let next_placement_side = if self.random.gen_bool(0.5) { Side::Back } else { Side::Lay };
         let next_placement_size =
             Size(rust_decimal::Decimal::new(self.random.gen_range(2..300), 0));
         let next_placement_tick = self.gen_new_tick(next_placement_side, prev_balance);
 
         self.next_placement_order = Order {
+            selection: next_placement_selection,
             tick: next_placement_tick,
             size: next_placement_size,
             side: next_placement_side,
@@ -143,17 +196,22 @@ impl BotActor {
     }
 
     fn gen_new_tick(&mut self, next_placement_side: Side, prev_balance: Tick) -> Tick {
-        let next_placement_tick_diff = {
+        let next_placement_tick_diff: i32 = {
             match next_placement_side {
                 Side::Back => self.random.gen_range(-2..=0),
                 Side::Lay => self.random.gen_range(0..=2),
             }
         };
-        let next_placement_tick = prev_balance
-            .0
-            .checked_add(rust_decimal::Decimal::new(next_placement_tick_diff, 2))
-            .unwrap_or(dec!(1.50));
-        Tick(next_placement_tick)
+        let mut next_placement_tick =
+            if prev_balance.is_valid() { prev_balance } else { Tick(dec!(1.50)) };
+        for _ in 0..next_placement_tick_diff.abs() {
+            let tick = match next_placement_tick_diff.is_negative() {
+                true => next_placement_tick.prev(),
+                false => next_placement_tick.next(),
+            };
+            next_placement_tick = tick.unwrap_or(next_placement_tick);
+        }
+        next_placement_tick
     }
 }
 
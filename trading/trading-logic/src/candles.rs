@@ -0,0 +1,150 @@
+use std::collections::{HashMap, VecDeque};
+
+use trading_types::common::{SelectionId, Size};
+use trading_types::from_server::{Candle, CandleInterval, Trade};
+
+/// Rolling OHLC candles of every selection at every supported interval
+#[derive(Debug, Clone)]
+pub struct Candles {
+    series: HashMap<(SelectionId, CandleInterval), VecDeque<Candle>>,
+    /// How many candles are kept per selection and interval
+    capacity: usize,
+}
+
+impl Candles {
+    pub fn new(capacity: usize) -> Self {
+        Self { series: HashMap::new(), capacity }
+    }
+
+    pub fn record(&mut self, trade: &Trade) {
+        for interval in CandleInterval::ALL {
+            let series = self.series.entry((trade.selection, interval)).or_default();
+            let start = interval.bucket_start(trade.timestamp);
+            match series.back_mut() {
+                Some(candle) if candle.start == start => {
+                    let turnover = candle.vwap * candle.volume.0 + trade.price.0 * trade.size.0;
+                    candle.high = candle.high.max(trade.price);
+                    candle.low = candle.low.min(trade.price);
+                    candle.close = trade.price;
+                    candle.volume.0 += trade.size.0;
+                    candle.vwap = turnover / candle.volume.0;
+                }
+                _ => {
+                    series.push_back(Candle {
+                        selection: trade.selection,
+                        interval,
+                        start,
+                        open: trade.price,
+                        high: trade.price,
+                        low: trade.price,
+                        close: trade.price,
+                        volume: Size(trade.size.0),
+                        vwap: trade.price.0,
+                    });
+                    while series.len() > self.capacity {
+                        series.pop_front();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Candles of all selections at the interval, oldest first
+    pub fn get(&self, interval: CandleInterval) -> Vec<Candle> {
+        let mut candles = self
+            .series
+            .iter()
+            .filter(|((_, series_interval), _)| *series_interval == interval)
+            .flat_map(|(_, series)| series.iter().cloned())
+            .collect::<Vec<_>>();
+        candles.sort_by_key(|candle| (candle.start, candle.selection));
+        candles
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use rust_decimal_macros::dec;
+    use trading_types::common::{Side, Tick};
+
+    use super::*;
+
+    fn trade(selection: u32, second: i64, price: Tick, size: Size) -> Trade {
+        Trade {
+            selection: SelectionId(selection),
+            price,
+            size,
+            aggressor_side: Side::Back,
+            timestamp: Utc.timestamp_opt(second, 0).unwrap(),
+            sequence: 0,
+        }
+    }
+
+    #[test]
+    fn trades_are_bucketed_per_interval() {
+        let mut candles = Candles::new(10);
+        candles.record(&trade(1, 0, Tick(dec!(2)), Size(dec!(10))));
+        candles.record(&trade(1, 5, Tick(dec!(3)), Size(dec!(30))));
+        candles.record(&trade(1, 12, Tick(dec!(2.5)), Size(dec!(10))));
+
+        let starts =
+            |interval| candles.get(interval).iter().map(|candle| candle.start).collect::<Vec<_>>();
+        assert_eq!(starts(CandleInterval::OneSecond), vec![0, 5, 12]);
+        assert_eq!(starts(CandleInterval::TenSeconds), vec![0, 10]);
+        assert_eq!(starts(CandleInterval::OneMinute), vec![0]);
+
+        let first = &candles.get(CandleInterval::TenSeconds)[0];
+        assert_eq!(
+            (first.open, first.high, first.low, first.close, first.volume),
+            (Tick(dec!(2)), Tick(dec!(3)), Tick(dec!(2)), Tick(dec!(3)), Size(dec!(40)))
+        );
+    }
+
+    #[test]
+    fn vwap_only_covers_trades_of_its_bucket() {
+        let mut candles = Candles::new(10);
+        candles.record(&trade(1, 8, Tick(dec!(2)), Size(dec!(10))));
+        candles.record(&trade(1, 9, Tick(dec!(3)), Size(dec!(30))));
+        candles.record(&trade(1, 10, Tick(dec!(2.5)), Size(dec!(10))));
+
+        let vwaps =
+            |interval| candles.get(interval).iter().map(|candle| candle.vwap).collect::<Vec<_>>();
+        // (2 * 10 + 3 * 30) / 40 before the boundary, the trade at 10s starts a new bucket
+        assert_eq!(vwaps(CandleInterval::TenSeconds), vec![dec!(2.75), dec!(2.5)]);
+        // (2 * 10 + 3 * 30 + 2.5 * 10) / 50
+        assert_eq!(vwaps(CandleInterval::OneMinute), vec![dec!(2.7)]);
+    }
+
+    #[test]
+    fn selections_have_their_own_series() {
+        let mut candles = Candles::new(10);
+        candles.record(&trade(2, 0, Tick(dec!(3)), Size(dec!(5))));
+        candles.record(&trade(1, 0, Tick(dec!(2)), Size(dec!(5))));
+
+        let selections = candles
+            .get(CandleInterval::OneSecond)
+            .iter()
+            .map(|candle| (candle.selection, candle.close))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            selections,
+            vec![(SelectionId(1), Tick(dec!(2))), (SelectionId(2), Tick(dec!(3)))]
+        );
+    }
+
+    #[test]
+    fn oldest_candles_are_dropped_past_the_capacity() {
+        let mut candles = Candles::new(2);
+        for second in 0..3 {
+            candles.record(&trade(1, second, Tick(dec!(2)), Size(dec!(5))));
+        }
+
+        let starts = candles
+            .get(CandleInterval::OneSecond)
+            .iter()
+            .map(|candle| candle.start)
+            .collect::<Vec<_>>();
+        assert_eq!(starts, vec![1, 2]);
+    }
+}
@@ -0,0 +1,992 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
+use trading_types::common::{
+    Fill, MarketStatus, Order, OrderRecord, RequestId, Selection, SelectionId, SelfTradePrevention,
+    Side, Size, Tick, TraderId,
+};
+use trading_types::from_server::{
+    Candle, CandleInterval, LadderSnapshot, Position, RejectReason, SelfTradePrevented, TickData,
+    Trade, TraderInfo, TraderOrders,
+};
+
+use crate::candles::Candles;
+use crate::order_book::{OrderBook, RestingOrder};
+use crate::position::BestPrices;
+use crate::settlement::Commission;
+use crate::snapshot::MarketSnapshot;
+use crate::validation::OrderRules;
+
+/// Settings of a single market
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MarketConfig {
+    pub rules: OrderRules,
+    /// Runners of the market, each traded on its own ladder
+    pub selections: Vec<Selection>,
+    /// When set, the market closes after this long, gets settled with a random winner and
+    /// reopens with an empty order book. Without it the market waits for `SettleMarket`
+    pub round_duration: Option<Duration>,
+    /// Play money every trader starts with
+    pub starting_balance: Size,
+    pub commission: Commission,
+    /// How many of the latest trades are kept for traders that join later
+    pub trade_history: usize,
+    /// How many candles are kept per selection and interval
+    pub candle_history: usize,
+    /// When unset, traders can match their own orders but that volume is left out of the ladder
+    /// totals and the trade tape
+    pub self_trade_prevention: Option<SelfTradePrevention>,
+    #[serde(default)]
+    pub disconnect_policy: DisconnectPolicy,
+}
+
+/// What happens to the open orders of a trader whose connection closed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DisconnectPolicy {
+    /// Cancel them right away
+    #[default]
+    CancelOrders,
+    /// Leave them in the book for a while, cancelling them unless the trader registers again with
+    /// the same id
+    KeepOrders(Duration),
+}
+
+impl Default for MarketConfig {
+    fn default() -> Self {
+        Self {
+            rules: OrderRules::default(),
+            selections: vec![
+                Selection { id: SelectionId(1), name: "Home".to_string() },
+                Selection { id: SelectionId(2), name: "Away".to_string() },
+            ],
+            round_duration: Some(Duration::from_secs(60)),
+            starting_balance: Size(dec!(1000)),
+            commission: Commission::default(),
+            trade_history: 50,
+            candle_history: 120,
+            self_trade_prevention: Some(SelfTradePrevention::CancelResting),
+            disconnect_policy: DisconnectPolicy::default(),
+        }
+    }
+}
+
+/// An instruction received by the market
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Command {
+    RegisterTrader {
+        trader: TraderId,
+    },
+    /// The trader is gone, its account gets dropped once it has nothing left in the market
+    DeregisterTrader {
+        trader: TraderId,
+    },
+    PlaceOrder {
+        trader: TraderId,
+        request_id: RequestId,
+        order: Order,
+    },
+    CancelOrder {
+        trader: TraderId,
+        request_id: RequestId,
+    },
+    CancelOrders {
+        trader: TraderId,
+        selection: Option<SelectionId>,
+        tick: Option<Tick>,
+    },
+    CashOut {
+        trader: TraderId,
+        request_id: RequestId,
+        selection: SelectionId,
+    },
+    SetStatus(MarketStatus),
+    Settle(SelectionId),
+}
+
+/// A change of the market state caused by a command
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Event {
+    TraderRegistered {
+        trader: TraderId,
+    },
+    /// The account of a departed trader got dropped
+    TraderRemoved {
+        trader: TraderId,
+    },
+    OrderAccepted {
+        trader: TraderId,
+        request_id: RequestId,
+        order: Order,
+    },
+    OrderRejected {
+        trader: TraderId,
+        request_id: RequestId,
+        reason: RejectReason,
+    },
+    OrderFilled {
+        trader: TraderId,
+        request_id: RequestId,
+        price: Tick,
+        matched: Size,
+        remaining: Size,
+        /// Whether the fill belongs to the incoming order rather than a resting one
+        aggressor: bool,
+    },
+    SelfTradePrevented {
+        trader: TraderId,
+        prevented: SelfTradePrevented,
+    },
+    OrderCancelled {
+        trader: TraderId,
+        request_id: RequestId,
+        size: Size,
+    },
+    /// A tick of the ladder changed, `sequence` orders the ladder updates
+    TickUpdated {
+        sequence: u64,
+        tick_data: TickData,
+    },
+    /// The tick that got matched last on the selection
+    LatestMatch {
+        sequence: u64,
+        tick_data: TickData,
+    },
+    Traded(Trade),
+    /// Every order got removed and the ladder starts from scratch
+    BookReset,
+    StatusChanged(MarketStatus),
+    TraderSettled {
+        trader: TraderId,
+        winner: SelectionId,
+        profit_and_loss: Size,
+        commission: Size,
+        balance: Size,
+    },
+}
+
+/// How much of an accepted order got matched immediately
+#[derive(Debug, Clone)]
+pub struct OrderPlaced {
+    pub fills: Vec<OrderFill>,
+    pub remaining: Size,
+}
+
+/// A single execution of an order at one price
+#[derive(Debug, Clone)]
+pub struct OrderFill {
+    pub request_id: RequestId,
+    pub price: Tick,
+    pub matched: Size,
+    pub remaining: Size,
+}
+
+#[derive(Debug, Clone)]
+pub struct InvalidTransition {
+    pub from: MarketStatus,
+    pub to: MarketStatus,
+}
+
+/// What a command resulted in, for whoever sent it
+#[derive(Debug, Clone)]
+pub enum Outcome {
+    /// The command got applied
+    Done,
+    OrderPlaced(OrderPlaced),
+    OrderRejected(RejectReason),
+    InvalidTransition(InvalidTransition),
+}
+
+impl Outcome {
+    /// The outcome of `PlaceOrder` or `CashOut`
+    pub fn into_order(self) -> Result<OrderPlaced, RejectReason> {
+        match self {
+            Outcome::OrderPlaced(placed) => Ok(placed),
+            Outcome::OrderRejected(reason) => Err(reason),
+            other => panic!("not the outcome of an order: {other:?}"),
+        }
+    }
+
+    /// The outcome of `SetStatus` or `Settle`
+    pub fn into_transition(self) -> Result<(), InvalidTransition> {
+        match self {
+            Outcome::Done => Ok(()),
+            Outcome::InvalidTransition(err) => Err(err),
+            other => panic!("not the outcome of a status change: {other:?}"),
+        }
+    }
+}
+
+impl From<Result<OrderPlaced, RejectReason>> for Outcome {
+    fn from(result: Result<OrderPlaced, RejectReason>) -> Self {
+        match result {
+            Ok(placed) => Outcome::OrderPlaced(placed),
+            Err(reason) => Outcome::OrderRejected(reason),
+        }
+    }
+}
+
+impl From<Result<(), InvalidTransition>> for Outcome {
+    fn from(result: Result<(), InvalidTransition>) -> Self {
+        match result {
+            Ok(()) => Outcome::Done,
+            Err(err) => Outcome::InvalidTransition(err),
+        }
+    }
+}
+
+/// Orders and money of a single trader
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraderAccount {
+    pub orders: HashMap<RequestId, OrderRecord>,
+    pub balance: Size,
+    /// The trader deregistered and the account is dropped once it is idle
+    #[serde(default)]
+    pub departed: bool,
+}
+
+impl TraderAccount {
+    /// No orders and the balance every trader starts with, so there is nothing to keep for when
+    /// the trader comes back
+    fn is_idle(&self, starting_balance: Size) -> bool {
+        self.orders.is_empty() && self.balance == starting_balance
+    }
+}
+
+/// Order books of a market together with the traders' orders. Every command goes through
+/// [`MatchingEngine::execute`], which applies it synchronously and returns the events it caused.
+pub struct MatchingEngine {
+    order_books: BTreeMap<SelectionId, OrderBook>,
+    traders: BTreeMap<TraderId, TraderAccount>,
+    config: MarketConfig,
+    status: MarketStatus,
+    trades: VecDeque<Trade>,
+    next_trade_sequence: u64,
+    candles: Candles,
+    /// Sequence number of the latest ladder update
+    sequence: u64,
+    latest_matches: BTreeMap<SelectionId, Tick>,
+    events: Vec<Event>,
+}
+
+/// Everything that changed in the market because of a single incoming order
+struct MatchResult {
+    placed: OrderPlaced,
+    passive_fills: Vec<(TraderId, OrderFill)>,
+    affected_ticks: Vec<Tick>,
+    trades: Vec<Trade>,
+    self_trades: Vec<SelfTradePrevented>,
+}
+
+impl Default for MatchingEngine {
+    fn default() -> Self {
+        Self::new(MarketConfig::default())
+    }
+}
+
+impl MatchingEngine {
+    pub fn new(config: MarketConfig) -> Self {
+        let order_books = config
+            .selections
+            .iter()
+            .map(|selection| (selection.id, OrderBook::new(selection.id)))
+            .collect();
+
+        let candles = Candles::new(config.candle_history);
+
+        Self {
+            order_books,
+            traders: BTreeMap::new(),
+            config,
+            status: MarketStatus::Inactive,
+            trades: VecDeque::new(),
+            next_trade_sequence: 0,
+            candles,
+            sequence: 0,
+            latest_matches: BTreeMap::new(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Rebuild the engine from a snapshot taken with [`MatchingEngine::market_snapshot`]
+    pub fn restore(snapshot: MarketSnapshot) -> Self {
+        let mut engine = Self::new(snapshot.config);
+        for level in snapshot.levels {
+            if let Some(order_book) = engine.order_books.get_mut(&level.selection) {
+                order_book.restore_level(level);
+            }
+        }
+        for trade in snapshot.trades.iter() {
+            engine.candles.record(trade);
+        }
+        engine.traders = snapshot.traders;
+        engine.status = snapshot.status;
+        engine.trades = snapshot.trades.into();
+        engine.next_trade_sequence = snapshot.next_trade_sequence;
+        engine.sequence = snapshot.sequence;
+        engine.latest_matches = snapshot.latest_matches;
+        engine
+    }
+
+    /// The state of the whole market, enough to restore it with [`MatchingEngine::restore`]
+    pub fn market_snapshot(&self) -> MarketSnapshot {
+        let levels = self
+            .order_books
+            .values()
+            .flat_map(|order_book| order_book.levels())
+            .filter(|obr| !obr.is_empty())
+            .cloned()
+            .collect();
+        MarketSnapshot {
+            config: self.config.clone(),
+            status: self.status,
+            sequence: self.sequence,
+            next_trade_sequence: self.next_trade_sequence,
+            latest_matches: self.latest_matches.clone(),
+            traders: self.traders.clone(),
+            trades: self.trades(),
+            levels,
+        }
+    }
+
+    /// Apply the command and return its outcome along with the events it caused
+    pub fn execute(&mut self, command: Command, now: DateTime<Utc>) -> (Outcome, Vec<Event>) {
+        let outcome = match command {
+            Command::RegisterTrader { trader } => {
+                self.register_trader(trader);
+                Outcome::Done
+            }
+            Command::DeregisterTrader { trader } => {
+                self.deregister_trader(&trader);
+                Outcome::Done
+            }
+            Command::PlaceOrder { trader, request_id, order } => {
+                self.place_order(trader, request_id, order, now).into()
+            }
+            Command::CancelOrder { trader, request_id } => {
+                self.cancel_order(&trader, &request_id);
+                Outcome::Done
+            }
+            Command::CancelOrders { trader, selection, tick } => {
+                self.cancel_orders(&trader, selection, tick);
+                Outcome::Done
+            }
+            Command::CashOut { trader, request_id, selection } => {
+                self.cash_out(trader, request_id, selection, now).into()
+            }
+            Command::SetStatus(status) => self.set_status(status).into(),
+            Command::Settle(winner) => self.settle(winner).into(),
+        };
+        (outcome, std::mem::take(&mut self.events))
+    }
+
+    pub fn config(&self) -> &MarketConfig {
+        &self.config
+    }
+
+    pub fn status(&self) -> MarketStatus {
+        self.status
+    }
+
+    pub fn trader(&self, trader_id: &TraderId) -> Option<&TraderAccount> {
+        self.traders.get(trader_id)
+    }
+
+    pub fn traders(&self) -> impl Iterator<Item = (&TraderId, &TraderAccount)> {
+        self.traders.iter()
+    }
+
+    /// The latest trades, oldest first
+    pub fn trades(&self) -> Vec<Trade> {
+        self.trades.iter().cloned().collect()
+    }
+
+    /// Recent candles of every selection at the given interval
+    pub fn candles(&self, interval: CandleInterval) -> Vec<Candle> {
+        self.candles.get(interval)
+    }
+
+    /// Open an account for the trader, a trader that already has one keeps it
+    fn register_trader(&mut self, trader: TraderId) {
+        let starting_balance = self.config.starting_balance;
+        let account = self.traders.entry(trader.clone()).or_insert_with(|| TraderAccount {
+            orders: HashMap::new(),
+            balance: starting_balance,
+            departed: false,
+        });
+        account.departed = false;
+        self.events.push(Event::TraderRegistered { trader });
+    }
+
+    /// Drop the account of the trader once it is idle. A trader that won or lost money keeps its
+    /// account, so that it gets its balance back when it registers again with the same id.
+    fn deregister_trader(&mut self, trader_id: &TraderId) {
+        if let Some(account) = self.traders.get_mut(trader_id) {
+            account.departed = true;
+        }
+        self.remove_if_idle(trader_id);
+    }
+
+    fn remove_if_idle(&mut self, trader_id: &TraderId) {
+        let starting_balance = self.config.starting_balance;
+        let idle = self
+            .traders
+            .get(trader_id)
+            .map_or(false, |account| account.departed && account.is_idle(starting_balance));
+        if idle {
+            self.traders.remove(trader_id);
+            self.events.push(Event::TraderRemoved { trader: trader_id.clone() });
+        }
+    }
+
+    /// Drop the accounts of every departed trader that is idle
+    fn remove_departed(&mut self) {
+        let starting_balance = self.config.starting_balance;
+        let departed = self
+            .traders
+            .iter()
+            .filter(|(_trader_id, account)| account.departed && account.is_idle(starting_balance))
+            .map(|(trader_id, _account)| trader_id.clone())
+            .collect::<Vec<_>>();
+        for trader in departed {
+            self.traders.remove(&trader);
+            self.events.push(Event::TraderRemoved { trader });
+        }
+    }
+
+    /// Status change requested from outside of the market
+    fn set_status(&mut self, status: MarketStatus) -> Result<(), InvalidTransition> {
+        // Settling has to go through `settle` so that the traders get paid out
+        if status == MarketStatus::Settled {
+            return Err(InvalidTransition { from: self.status, to: status })
+        }
+        self.transition(status)
+    }
+
+    /// Pay out all matched orders against the winning selection and move the closed market to
+    /// `Settled`
+    fn settle(&mut self, winner: SelectionId) -> Result<(), InvalidTransition> {
+        if !self.status.can_transition_to(MarketStatus::Settled) {
+            return Err(InvalidTransition { from: self.status, to: MarketStatus::Settled })
+        }
+
+        for (trader_id, trader) in self.traders.iter_mut() {
+            let profit_and_loss =
+                crate::settlement::profit_and_loss(trader.orders.values(), winner);
+            let commission = self.config.commission.charge(trader_id, profit_and_loss);
+            trader.balance.0 += profit_and_loss - commission;
+            self.events.push(Event::TraderSettled {
+                trader: trader_id.clone(),
+                winner,
+                profit_and_loss: Size(profit_and_loss),
+                commission: Size(commission),
+                balance: trader.balance,
+            });
+        }
+        self.transition(MarketStatus::Settled)
+    }
+
+    fn transition(&mut self, status: MarketStatus) -> Result<(), InvalidTransition> {
+        if !self.status.can_transition_to(status) {
+            return Err(InvalidTransition { from: self.status, to: status })
+        }
+        self.status = status;
+
+        match status {
+            // Unmatched orders lapse once the market is closed
+            MarketStatus::Closed => {
+                let trader_ids = self.traders.keys().cloned().collect::<Vec<_>>();
+                for trader_id in trader_ids {
+                    self.cancel_matching(&trader_id, |_selection, _tick, _request_id| true);
+                }
+            }
+            MarketStatus::Inactive => self.reset(),
+            _ => {}
+        }
+        self.events.push(Event::StatusChanged(status));
+        Ok(())
+    }
+
+    /// Clear the order book and all trader orders
+    fn reset(&mut self) {
+        for order_book in self.order_books.values_mut() {
+            order_book.clear();
+        }
+        for trader in self.traders.values_mut() {
+            trader.orders.clear();
+        }
+        self.remove_departed();
+
+        let new_balance = Tick(dec!(1.50));
+        self.latest_matches =
+            self.order_books.keys().map(|selection| (*selection, new_balance)).collect();
+        self.trades.clear();
+        self.events.push(Event::BookReset);
+    }
+
+    /// Place the order that equalises the profit of the trader's position on the selection
+    fn cash_out(
+        &mut self,
+        trader: TraderId,
+        request_id: RequestId,
+        selection: SelectionId,
+        now: DateTime<Utc>,
+    ) -> Result<OrderPlaced, RejectReason> {
+        let Some(account) = self.traders.get(&trader) else {
+            return Err(RejectReason::UnknownTrader)
+        };
+        let hedge = crate::position::position(
+            account.orders.values(),
+            selection,
+            self.best_prices(selection),
+            &self.config.rules,
+        )
+        .and_then(|position| position.cash_out);
+        let Some(hedge) = hedge else {
+            let reason = RejectReason::NothingToCashOut;
+            self.events.push(Event::OrderRejected { trader, request_id, reason: reason.clone() });
+            return Err(reason)
+        };
+
+        let order = Order { selection, tick: hedge.price, size: hedge.size, side: hedge.side };
+        self.place_order(trader, request_id, order, now)
+    }
+
+    fn place_order(
+        &mut self,
+        trader: TraderId,
+        request_id: RequestId,
+        order: Order,
+        now: DateTime<Utc>,
+    ) -> Result<OrderPlaced, RejectReason> {
+        let result = self.try_place_order(&trader, &request_id, &order, now);
+        if let Err(reason) = &result {
+            self.events.push(Event::OrderRejected { trader, request_id, reason: reason.clone() });
+        }
+        result
+    }
+
+    fn try_place_order(
+        &mut self,
+        trader_id: &TraderId,
+        request_id: &RequestId,
+        order: &Order,
+        now: DateTime<Utc>,
+    ) -> Result<OrderPlaced, RejectReason> {
+        let Some(trader) = self.traders.get(trader_id) else {
+            return Err(RejectReason::UnknownTrader)
+        };
+        self.config.rules.validate(order, self.status.is_tradable(), &self.config.selections)?;
+        if trader.orders.contains_key(request_id) {
+            return Err(RejectReason::DuplicateRequestId)
+        }
+        // Reserve the worst case liability of the new order
+        let candidate = OrderRecord::new(request_id.clone(), order);
+        let exposure = crate::exposure::exposure(
+            trader.orders.values().chain(std::iter::once(&candidate)),
+            self.config.selections.iter().map(|selection| selection.id),
+        );
+        if trader.balance.0 < exposure {
+            let available = Size(trader.balance.0 - self.exposure(trader));
+            return Err(RejectReason::InsufficientFunds { available })
+        }
+        let Some(order_book) = self.order_books.get(&order.selection) else {
+            return Err(RejectReason::UnknownSelection)
+        };
+        if !order_book.contains(&order.tick) {
+            return Err(RejectReason::UnknownTick)
+        }
+
+        self.events.push(Event::OrderAccepted {
+            trader: trader_id.clone(),
+            request_id: request_id.clone(),
+            order: order.clone(),
+        });
+        let MatchResult { placed, passive_fills, affected_ticks, trades, self_trades } =
+            self.match_orders(trader_id, request_id, order, now);
+        let last_matched_tick = trades.last().map(|trade| trade.price);
+        for fill in placed.fills.iter() {
+            self.events.push(Event::OrderFilled {
+                trader: trader_id.clone(),
+                request_id: fill.request_id.clone(),
+                price: fill.price,
+                matched: fill.matched,
+                remaining: fill.remaining,
+                aggressor: true,
+            });
+        }
+
+        let mut record = candidate;
+        for fill in placed.fills.iter() {
+            record.add_fill(Fill { price: fill.price, size: fill.matched });
+        }
+        record.remaining_size = placed.remaining;
+        if let Some(trader) = self.traders.get_mut(trader_id) {
+            if record.matched_size.0 > dec!(0) || record.remaining_size.0 > dec!(0) {
+                trader.orders.insert(request_id.clone(), record);
+            }
+            for self_trade in self_trades {
+                if let Some(resting) = trader.orders.get_mut(&self_trade.resting) {
+                    resting.remaining_size.0 -= self_trade.resting_cancelled.0;
+                    if resting.matched_size.0 == dec!(0) && resting.remaining_size.0 == dec!(0) {
+                        trader.orders.remove(&self_trade.resting);
+                    }
+                }
+                self.events.push(Event::SelfTradePrevented {
+                    trader: trader_id.clone(),
+                    prevented: self_trade,
+                });
+            }
+        }
+
+        for (trader_id, fill) in passive_fills {
+            let Some(trader) = self.traders.get_mut(&trader_id) else {
+                continue;
+            };
+
+            if let Some(record) = trader.orders.get_mut(&fill.request_id) {
+                record.add_fill(Fill { price: fill.price, size: fill.matched });
+            }
+            self.events.push(Event::OrderFilled {
+                trader: trader_id,
+                request_id: fill.request_id,
+                price: fill.price,
+                matched: fill.matched,
+                remaining: fill.remaining,
+                aggressor: false,
+            });
+        }
+
+        for tick in affected_ticks {
+            let Some(obr) =
+                self.order_books.get(&order.selection).and_then(|order_book| order_book.level(&tick))
+            else {
+                continue;
+            };
+            let tick_data = obr.tick_data();
+            if Some(tick) == last_matched_tick {
+                self.publish_latest_match(tick_data.clone());
+            }
+            self.publish_tick_update(tick_data);
+        }
+        self.events.extend(trades.into_iter().map(Event::Traded));
+
+        Ok(placed)
+    }
+
+    /// Match the incoming order against the opposite side of the book in price-time priority.
+    ///
+    /// A back is matched against lays at its tick or above (highest first), a lay against backs
+    /// at its tick or below (lowest first). Fills happen at the price of the resting order and
+    /// whatever is left of the incoming order rests at its own tick. Every fill against a resting
+    /// order is recorded as a trade.
+    fn match_orders(
+        &mut self,
+        trader_id: &TraderId,
+        request_id: &RequestId,
+        order: &Order,
+        now: DateTime<Utc>,
+    ) -> MatchResult {
+        let tick = order.tick;
+        let self_trade_prevention = self.config.self_trade_prevention;
+        let order_book = self
+            .order_books
+            .entry(order.selection)
+            .or_insert_with(|| OrderBook::new(order.selection));
+
+        let mut fills = vec![];
+        let mut passive_fills = vec![];
+        let mut affected_ticks = vec![];
+        let mut self_trades = vec![];
+        let mut leftover_amount = order.size;
+
+        for level_tick in order_book.matchable_ticks(order.side, tick) {
+            if leftover_amount.0 == dec!(0) {
+                break
+            }
+            let Some(obr) = order_book.level_mut(&level_tick) else {
+                continue;
+            };
+            let opposing_orders = match order.side {
+                Side::Back => &mut obr.open_lays,
+                Side::Lay => &mut obr.open_backs,
+            };
+
+            let mut matched_amount = Size(dec!(0));
+            let mut wash_amount = Size(dec!(0));
+            let mut level_changed = false;
+            let mut index = 0;
+            while leftover_amount.0 > dec!(0) {
+                let Some(resting) = opposing_orders.get(index) else {
+                    break;
+                };
+                let resting_size = resting.size;
+                let is_self_trade = &resting.trader == trader_id;
+                if let (true, Some(mode)) = (is_self_trade, self_trade_prevention) {
+                    let (incoming_cancelled, resting_cancelled) = match mode {
+                        SelfTradePrevention::CancelResting => (Size(dec!(0)), resting_size),
+                        SelfTradePrevention::CancelIncoming => (leftover_amount, Size(dec!(0))),
+                        SelfTradePrevention::DecrementBoth => {
+                            let size = std::cmp::min(leftover_amount, resting_size);
+                            (size, size)
+                        }
+                    };
+                    leftover_amount.0 -= incoming_cancelled.0;
+                    level_changed |= resting_cancelled.0 > dec!(0);
+                    self_trades.push(SelfTradePrevented {
+                        mode,
+                        incoming: request_id.clone(),
+                        resting: resting.request_id.clone(),
+                        incoming_cancelled,
+                        resting_cancelled,
+                    });
+                    if !opposing_orders.reduce(index, resting_cancelled) {
+                        index += 1;
+                    }
+                    continue
+                }
+
+                let filled = std::cmp::min(leftover_amount, resting_size);
+                matched_amount.0 += filled.0;
+                leftover_amount.0 -= filled.0;
+                if is_self_trade {
+                    wash_amount.0 += filled.0;
+                }
+                passive_fills.push((
+                    resting.trader.clone(),
+                    OrderFill {
+                        request_id: resting.request_id.clone(),
+                        price: level_tick,
+                        matched: filled,
+                        remaining: Size(resting_size.0 - filled.0),
+                    },
+                ));
+                if !opposing_orders.reduce(index, filled) {
+                    index += 1;
+                }
+            }
+            if matched_amount.0 > dec!(0) {
+                // Matching against yourself doesn't count as traded volume
+                obr.total_matched.0 += matched_amount.0 - wash_amount.0;
+                fills.push(OrderFill {
+                    request_id: request_id.clone(),
+                    price: level_tick,
+                    matched: matched_amount,
+                    remaining: leftover_amount,
+                });
+            }
+            order_book.refresh(level_tick);
+            if matched_amount.0 > dec!(0) || level_changed {
+                affected_ticks.push(level_tick);
+            }
+        }
+
+        if leftover_amount.0 > dec!(0) {
+            if let Some(obr) = order_book.level_mut(&tick) {
+                let aligned_orders = match order.side {
+                    Side::Back => &mut obr.open_backs,
+                    Side::Lay => &mut obr.open_lays,
+                };
+                aligned_orders.push_back(RestingOrder {
+                    trader: trader_id.clone(),
+                    request_id: request_id.clone(),
+                    size: leftover_amount,
+                });
+                order_book.refresh(tick);
+                if !affected_ticks.contains(&tick) {
+                    affected_ticks.push(tick);
+                }
+            }
+        }
+
+        let trades = passive_fills
+            .iter()
+            .filter(|(passive_trader_id, _)| passive_trader_id != trader_id)
+            .map(|(_, fill)| self.record_trade(order, fill, now))
+            .collect::<Vec<_>>();
+        let placed = OrderPlaced { fills, remaining: leftover_amount };
+        MatchResult { placed, passive_fills, affected_ticks, trades, self_trades }
+    }
+
+    fn record_trade(&mut self, order: &Order, fill: &OrderFill, now: DateTime<Utc>) -> Trade {
+        let trade = Trade {
+            selection: order.selection,
+            price: fill.price,
+            size: fill.matched,
+            aggressor_side: order.side,
+            timestamp: now,
+            sequence: self.next_trade_sequence,
+        };
+        self.next_trade_sequence += 1;
+
+        self.candles.record(&trade);
+        self.trades.push_back(trade.clone());
+        while self.trades.len() > self.config.trade_history {
+            self.trades.pop_front();
+        }
+        trade
+    }
+
+    fn cancel_order(&mut self, trader_id: &TraderId, request_id: &RequestId) {
+        self.cancel_matching(trader_id, |_selection, _tick, order_request_id| {
+            order_request_id == request_id
+        })
+    }
+
+    /// Cancel every open order of the trader, optionally limited to a single selection or tick
+    fn cancel_orders(
+        &mut self,
+        trader_id: &TraderId,
+        selection: Option<SelectionId>,
+        tick: Option<Tick>,
+    ) {
+        self.cancel_matching(trader_id, |order_selection, order_tick, _request_id| {
+            selection.map_or(true, |selection| selection == order_selection) &&
+                tick.map_or(true, |tick| tick == order_tick)
+        })
+    }
+
+    /// Remove the unmatched remainder of all the trader's orders that satisfy the filter
+    fn cancel_matching(
+        &mut self,
+        trader_id: &TraderId,
+        filter: impl Fn(SelectionId, Tick, &RequestId) -> bool,
+    ) {
+        let Some(trader) = self.traders.get_mut(trader_id) else {
+            return;
+        };
+
+        // Only the ticks the trader has unmatched orders at need to be visited
+        let mut to_cancel = BTreeMap::<(SelectionId, Tick), Vec<RequestId>>::new();
+        for record in trader.orders.values() {
+            if record.remaining_size.0 > dec!(0) &&
+                filter(record.selection, record.tick, &record.request_id)
+            {
+                to_cancel
+                    .entry((record.selection, record.tick))
+                    .or_default()
+                    .push(record.request_id.clone());
+            }
+        }
+
+        let mut updated_ticks = vec![];
+        for ((selection, tick), request_ids) in to_cancel {
+            let Some(order_book) = self.order_books.get_mut(&selection) else {
+                continue;
+            };
+            let cancelled = order_book.cancel(tick, |trader, request_id| {
+                trader == trader_id && request_ids.contains(request_id)
+            });
+            if cancelled.is_empty() {
+                continue
+            }
+
+            for (request_id, size) in cancelled {
+                self.events.push(Event::OrderCancelled {
+                    trader: trader_id.clone(),
+                    request_id: request_id.clone(),
+                    size,
+                });
+                let Some(record) = trader.orders.get_mut(&request_id) else {
+                    continue;
+                };
+                record.remaining_size.0 -= size.0;
+                // Nothing left to show for an order that got cancelled before any matching
+                if record.matched_size.0 == dec!(0) && record.remaining_size.0 == dec!(0) {
+                    trader.orders.remove(&request_id);
+                }
+            }
+            if let Some(obr) = order_book.level(&tick) {
+                updated_ticks.push(obr.tick_data());
+            }
+        }
+        for tick_data in updated_ticks {
+            self.publish_tick_update(tick_data);
+        }
+        self.remove_if_idle(trader_id);
+    }
+
+    /// Record a changed tick as the next ladder update
+    fn publish_tick_update(&mut self, tick_data: TickData) {
+        self.sequence += 1;
+        self.events.push(Event::TickUpdated { sequence: self.sequence, tick_data });
+    }
+
+    fn publish_latest_match(&mut self, tick_data: TickData) {
+        self.sequence += 1;
+        self.latest_matches.insert(tick_data.selection, tick_data.tick);
+        self.events.push(Event::LatestMatch { sequence: self.sequence, tick_data });
+    }
+
+    /// Worst case loss of the trader's orders over every possible winner
+    fn exposure(&self, trader: &TraderAccount) -> rust_decimal::Decimal {
+        crate::exposure::exposure(
+            trader.orders.values(),
+            self.config.selections.iter().map(|selection| selection.id),
+        )
+    }
+
+    /// Orders, balance and exposure of the trader
+    pub fn trader_state(&self, trader_id: &TraderId) -> Option<(TraderOrders, TraderInfo)> {
+        let trader = self.traders.get(trader_id)?;
+        let orders = TraderOrders { orders: trader.orders.values().cloned().collect() };
+        let info = TraderInfo { balance: trader.balance, exposure: Size(self.exposure(trader)) };
+        Some((orders, info))
+    }
+
+    /// Whether the trader has matched orders on the selection
+    pub fn has_position(&self, trader_id: &TraderId, selection: SelectionId) -> bool {
+        self.traders.get(trader_id).map_or(false, |trader| {
+            trader
+                .orders
+                .values()
+                .any(|order| order.selection == selection && order.matched_size.0 > dec!(0))
+        })
+    }
+
+    /// Positions of the trader on every selection with matched orders
+    pub fn positions(&self, trader_id: &TraderId) -> Vec<Position> {
+        let Some(trader) = self.traders.get(trader_id) else {
+            return vec![]
+        };
+        self.config
+            .selections
+            .iter()
+            .filter_map(|selection| {
+                crate::position::position(
+                    trader.orders.values(),
+                    selection.id,
+                    self.best_prices(selection.id),
+                    &self.config.rules,
+                )
+            })
+            .collect()
+    }
+
+    fn best_prices(&self, selection: SelectionId) -> BestPrices {
+        let Some(order_book) = self.order_books.get(&selection) else {
+            return BestPrices::default()
+        };
+        BestPrices { back: order_book.best_back(), lay: order_book.best_lay() }
+    }
+
+    /// The whole ladder as of the latest ladder update
+    pub fn snapshot(&self) -> LadderSnapshot {
+        let ticks = self
+            .order_books
+            .values()
+            .flat_map(|order_book| order_book.levels())
+            .map(|obr| obr.tick_data())
+            .collect::<Vec<_>>();
+        let latest_matches = self
+            .latest_matches
+            .iter()
+            .filter_map(|(selection, tick)| self.order_books.get(selection)?.level(tick))
+            .map(|obr| obr.tick_data())
+            .collect();
+
+        LadderSnapshot { sequence: self.sequence, ticks, latest_matches }
+    }
+}
@@ -0,0 +1,87 @@
+use rust_decimal::Decimal;
+use trading_types::common::{OrderRecord, SelectionId, Side, Size, Tick};
+
+use crate::settlement::profit_and_loss;
+
+/// The most an order of the given size can lose: the stake for a back and the payout for a lay
+pub fn liability(side: Side, size: Size, price: Tick) -> Decimal {
+    match side {
+        Side::Back => size.0,
+        Side::Lay => size.0 * (price.0 - Decimal::ONE),
+    }
+}
+
+/// Funds the trader needs to cover the worst case of their orders across every possible winner.
+/// Matched orders are netted against each other. An unmatched order might still get matched in
+/// full or not at all, so it only counts towards the winners it would lose on.
+pub fn exposure<'a>(
+    orders: impl IntoIterator<Item = &'a OrderRecord> + Clone,
+    selections: impl IntoIterator<Item = SelectionId>,
+) -> Decimal {
+    let worst_case = selections
+        .into_iter()
+        .map(|winner| {
+            profit_and_loss(orders.clone(), winner) - unmatched_loss(orders.clone(), winner)
+        })
+        .min()
+        .unwrap_or_default();
+
+    (-worst_case).max(Decimal::ZERO)
+}
+
+/// What the unmatched orders lose if all of them that lose on the winner get matched
+fn unmatched_loss<'a>(
+    orders: impl IntoIterator<Item = &'a OrderRecord>,
+    winner: SelectionId,
+) -> Decimal {
+    orders
+        .into_iter()
+        .filter(|order| match order.side {
+            Side::Back => order.selection != winner,
+            Side::Lay => order.selection == winner,
+        })
+        .map(|order| liability(order.side, order.remaining_size, order.tick))
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal_macros::dec;
+
+    use super::*;
+    use crate::testing::{back, check_cases, lay, OrderBuilder};
+
+    #[test]
+    fn exposure_is_the_worst_case_loss() {
+        let cases: [(Vec<OrderBuilder>, Decimal); 8] = [
+            (vec![], dec!(0)),
+            (vec![back(1, dec!(3)).unmatched(dec!(100))], dec!(100)),
+            (vec![lay(1, dec!(3)).unmatched(dec!(100))], dec!(200)),
+            (vec![back(1, dec!(2)).matched(dec!(40)).unmatched(dec!(60))], dec!(100)),
+            // Only one of the selections can lose
+            (
+                vec![back(1, dec!(3)).unmatched(dec!(100)), back(2, dec!(3)).unmatched(dec!(100))],
+                dec!(100),
+            ),
+            (
+                vec![back(1, dec!(3)).matched(dec!(100)), lay(1, dec!(2)).matched(dec!(100))],
+                dec!(0),
+            ),
+            // A hedge that is yet to be matched only adds to the winner it loses on
+            (
+                vec![
+                    back(1, dec!(2)).matched(dec!(1000)),
+                    lay(1, dec!(1.5)).unmatched(dec!(1333.33)),
+                ],
+                dec!(1000),
+            ),
+            (
+                vec![back(1, dec!(2)).matched(dec!(100)), lay(1, dec!(5)).unmatched(dec!(100))],
+                dec!(300),
+            ),
+        ];
+        check_cases(cases, |orders| {
+            exposure(orders.iter().map(OrderBuilder::record), [SelectionId(1), SelectionId(2)])
+        });
+    }
+}
@@ -0,0 +1,55 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, Write};
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::engine::{Command, Event};
+use crate::snapshot::MarketSnapshot;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum JournalEntry {
+    /// A market got started in the given state, every following entry belongs to it
+    Started(Box<MarketSnapshot>),
+    /// A command together with the time it got applied at
+    Command {
+        at: DateTime<Utc>,
+        command: Command,
+    },
+    Event(Event),
+}
+
+/// Append-only file of CBOR encoded journal entries
+pub struct Journal {
+    file: File,
+}
+
+impl Journal {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self { file })
+    }
+
+    pub fn append(&mut self, entry: &JournalEntry) -> io::Result<()> {
+        let mut writer = Vec::new();
+        ciborium::into_writer(entry, &mut writer)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+        self.file.write_all(&writer)
+    }
+
+    /// Every entry of the journal file. A partially written last entry is ignored.
+    pub fn read(path: impl AsRef<Path>) -> io::Result<Vec<JournalEntry>> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut entries = Vec::new();
+        loop {
+            match ciborium::from_reader::<JournalEntry, _>(&mut reader) {
+                Ok(entry) => entries.push(entry),
+                Err(ciborium::de::Error::Io(err)) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                    return Ok(entries)
+                }
+                Err(err) => return Err(io::Error::new(io::ErrorKind::InvalidData, err.to_string())),
+            }
+        }
+    }
+}
@@ -1,4 +1,17 @@
-#![feature(drain_filter)]
-
+#[cfg(feature = "actor")]
 mod bot;
+pub mod candles;
+pub mod engine;
+pub mod exposure;
+pub mod journal;
+#[cfg(feature = "actor")]
 pub mod market;
+mod order_book;
+pub mod position;
+#[cfg(feature = "actor")]
+mod publisher;
+pub mod settlement;
+pub mod snapshot;
+#[cfg(test)]
+mod testing;
+pub mod validation;
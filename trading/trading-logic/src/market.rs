@@ -1,253 +1,411 @@
 use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 
-use actix::{Actor, Addr, AsyncContext, Context, Handler, Message, Recipient};
+use actix::{Actor, Addr, AsyncContext, Context, Handler, Message, Recipient, SpawnHandle};
+use chrono::{DateTime, Utc};
+use futures::Stream;
 use nanoid::nanoid;
-use rust_decimal_macros::dec;
-use trading_types::common::{Order, RequestId, Size, Tick, TraderId};
-use trading_types::from_server::TickData;
+use rand::Rng;
+use tokio::sync::broadcast;
+use trading_types::common::{
+    MarketStatus, Order, RequestId, Selection, SelectionId, Tick, TraderId,
+};
+use trading_types::from_server::{
+    Candle, CandleInterval, LadderDelta, LadderSnapshot, Position, RejectReason,
+    SelfTradePrevented, ServerMessage, Settlement, Trade, TraderInfo, TraderOrders,
+};
 
-use self::messages::PlaceOrder;
 use crate::bot::BotActor;
+pub use crate::engine::MarketConfig;
+use crate::engine::{Command, DisconnectPolicy, Event, MatchingEngine, Outcome};
+use crate::journal::{Journal, JournalEntry};
+use crate::publisher::LadderPublisher;
+use crate::snapshot::MarketSnapshot;
 
 pub mod messages {
 
     use super::*;
+    pub use crate::engine::{InvalidTransition, OrderFill, OrderPlaced};
 
     #[derive(Message, Debug, Clone)]
-    #[rtype(result = "()")]
+    #[rtype(result = "Result<OrderPlaced, RejectReason>")]
     pub struct PlaceOrder {
         pub trader: TraderId,
         pub request_id: RequestId,
         pub order: Order,
     }
 
+    #[derive(Message, Debug, Clone)]
+    #[rtype(result = "()")]
+    pub struct CancelOrder {
+        pub trader: TraderId,
+        pub request_id: RequestId,
+    }
+
+    /// Cancel every open order of the trader, optionally limited to a single selection or tick
+    #[derive(Message, Debug, Clone)]
+    #[rtype(result = "()")]
+    pub struct CancelOrders {
+        pub trader: TraderId,
+        pub selection: Option<SelectionId>,
+        pub tick: Option<Tick>,
+    }
+
+    /// Place the order that equalises the profit of the trader's position on the selection
+    #[derive(Message, Debug, Clone)]
+    #[rtype(result = "Result<OrderPlaced, RejectReason>")]
+    pub struct CashOut {
+        pub trader: TraderId,
+        pub request_id: RequestId,
+        pub selection: SelectionId,
+    }
+
+    #[derive(Message, Debug, Clone)]
+    #[rtype(result = "Result<(), InvalidTransition>")]
+    pub struct SetMarketStatus(pub MarketStatus);
+
+    /// Pay out all matched orders against the winning selection and move the closed market to
+    /// `Settled`
+    #[derive(Message, Debug, Clone)]
+    #[rtype(result = "Result<(), InvalidTransition>")]
+    pub struct SettleMarket(pub SelectionId);
+
+    /// Recent candles of every selection at the given interval
+    #[derive(Message, Debug, Clone)]
+    #[rtype(result = "Vec<Candle>")]
+    pub struct GetCandles(pub CandleInterval);
+
     #[derive(Message, Debug, Clone)]
     #[rtype(result = "()")]
     pub struct SpawnBot;
 
+    /// Write the state of the market to its snapshot file right away
+    #[derive(Message, Debug, Clone)]
+    #[rtype(result = "std::io::Result<()>")]
+    pub struct SaveSnapshot;
+
+    /// Send the trader everything needed to show the market again, after it missed market data or
+    /// noticed a gap in the sequence numbers
     #[derive(Message, Debug, Clone)]
     #[rtype(result = "()")]
+    pub struct RequestMarketState(pub TraderId);
+
+    /// Start sending the trader updates. Market wide updates go out through the returned
+    /// [`MarketDataReceiver`], everything else to the recipients. Refused while another
+    /// connection is registered with the same trader id.
+    #[derive(Message, Debug, Clone)]
+    #[rtype(result = "Result<MarketDataReceiver, AlreadyConnected>")]
     pub struct RegisterTrader(
         pub TraderId,
         pub Recipient<TickDataUpdate>,
-        pub Recipient<OrderStateUpdate>,
+        pub Recipient<TraderUpdate>,
     );
 
+    /// Another connection is registered with the trader id
+    #[derive(Debug, Clone)]
+    pub struct AlreadyConnected;
+
+    /// Stop sending the trader updates. Its open orders are dealt with according to the market's
+    /// [`DisconnectPolicy`] and its account is dropped once it has nothing left in the market.
+    /// Ignored unless the recipient is the one the trader is registered with.
+    #[derive(Message, Debug, Clone)]
+    #[rtype(result = "()")]
+    pub struct DeregisterTrader(pub TraderId, pub Recipient<TraderUpdate>);
+
+    /// Updates that are only relevant to a single trader
     #[derive(Message, Debug, Clone)]
     #[rtype(result = "()")]
-    pub struct OrderStateUpdate {
-        pub open_orders: HashMap<Tick, Order>,
-        pub matched_orders: HashMap<Tick, Order>,
+    pub enum TraderUpdate {
+        OrderState(TraderOrders),
+        OrderFilled(OrderFill),
+        SelfTradePrevented(SelfTradePrevented),
+        Settlement(Settlement),
+        TraderInfo(TraderInfo),
+        Positions(Vec<Position>),
     }
 
     #[derive(Message, Debug, Clone)]
     #[rtype(result = "()")]
     pub enum TickDataUpdate {
-        SetRefresh(Vec<TickData>),
-        SingleUpdate(TickData),
-        NewLatestMatch(TickData),
+        Selections(Vec<Selection>),
+        SetRefresh(LadderSnapshot),
+        LadderDelta(LadderDelta),
+        Trade(Trade),
+        TradeHistory(Vec<Trade>),
+        MarketStatus(MarketStatus),
+    }
+}
+
+/// A market wide update, encoded once and shared by every trader watching the market
+#[derive(Debug)]
+pub struct MarketData {
+    pub update: messages::TickDataUpdate,
+    /// The update as it goes out over the websocket. Every connection still copies it, as a
+    /// binary websocket message owns its payload.
+    pub encoded: Vec<u8>,
+}
+
+impl MarketData {
+    /// `None` if the update can't be encoded
+    fn new(update: messages::TickDataUpdate) -> Option<Self> {
+        let mut encoded = Vec::new();
+        if let Err(err) = ciborium::into_writer(&ServerMessage::from(update.clone()), &mut encoded)
+        {
+            tracing::error!(err = ?err, "Failed to encode market data");
+            return None
+        }
+        Some(Self { update, encoded })
+    }
+}
+
+impl From<messages::TickDataUpdate> for ServerMessage {
+    fn from(update: messages::TickDataUpdate) -> Self {
+        use messages::TickDataUpdate;
+        match update {
+            TickDataUpdate::Selections(selections) => ServerMessage::Selections(selections),
+            TickDataUpdate::SetRefresh(snapshot) => ServerMessage::TickSetWhole(snapshot),
+            TickDataUpdate::LadderDelta(delta) => ServerMessage::LadderDelta(delta),
+            TickDataUpdate::Trade(trade) => ServerMessage::Trade(trade),
+            TickDataUpdate::TradeHistory(trades) => ServerMessage::TradeHistory(trades),
+            TickDataUpdate::MarketStatus(status) => ServerMessage::MarketStatus(status),
+        }
     }
 }
 
+/// How many market data updates a trader can fall behind before missing some
+const MARKET_DATA_CAPACITY: usize = 256;
+
+/// The market data of a single trader
+pub struct MarketDataReceiver(broadcast::Receiver<Arc<MarketData>>);
+
+impl MarketDataReceiver {
+    /// The updates in order. An error tells how many updates the trader fell behind and missed,
+    /// the stream ends with the market.
+    pub fn into_stream(self) -> impl Stream<Item = Result<Arc<MarketData>, u64>> {
+        futures::stream::unfold(self.0, |mut receiver| async move {
+            match receiver.recv().await {
+                Ok(data) => Some((Ok(data), receiver)),
+                Err(broadcast::error::RecvError::Lagged(missed)) => Some((Err(missed), receiver)),
+                Err(broadcast::error::RecvError::Closed) => None,
+            }
+        })
+    }
+}
+
+/// Runs a [`MatchingEngine`] and sends the events of every command to the traders
 pub struct MarketActor {
-    order_book: HashMap<Tick, OrderBookRange>,
-    traders: HashMap<TraderId, InternalTraderState>,
+    engine: MatchingEngine,
+    traders: HashMap<TraderId, TraderListener>,
     bots: Vec<Addr<BotActor>>,
+    journal: Option<Journal>,
+    snapshots: Option<Snapshots>,
+    publisher: LadderPublisher,
+    publish_interval: Duration,
+    market_data: broadcast::Sender<Arc<MarketData>>,
+    /// Timers cancelling the orders of disconnected traders once their grace period is over
+    grace_periods: HashMap<TraderId, SpawnHandle>,
 }
 
-struct InternalTraderState {
+/// How often the ladder changes are sent to the traders by default
+pub const PUBLISH_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How long the traders of a restored market have to connect again before their orders get
+/// cancelled
+const RESTORED_GRACE_PERIOD: Duration = Duration::from_secs(60);
+
+/// Where and how often the market writes its snapshot
+struct Snapshots {
+    path: PathBuf,
+    interval: Duration,
+}
+
+/// Where the updates for a single trader go
+struct TraderListener {
     recp_tick_update: Recipient<messages::TickDataUpdate>,
-    recp_order_update: Recipient<messages::OrderStateUpdate>,
-    open_orders: HashMap<Tick, Order>,
-    matched_orders: HashMap<Tick, Order>,
+    recp_order_update: Recipient<messages::TraderUpdate>,
 }
 
 impl Actor for MarketActor {
     type Context = Context<Self>;
 
     fn started(&mut self, ctx: &mut Self::Context) {
-        // Reset the game every minute
-        ctx.run_interval(std::time::Duration::from_secs(60), |act, _ctx| {
-            for (_key, val) in act.order_book.iter_mut() {
-                val.clear();
-            }
-            for (_key, val) in act.traders.iter_mut() {
-                val.clear();
-            }
+        // Traders of a restored market are not connected, until they register again. They get
+        // some time for that whatever the policy, a restart is not their doing.
+        let grace_period = match self.engine.config().disconnect_policy {
+            DisconnectPolicy::CancelOrders => RESTORED_GRACE_PERIOD,
+            DisconnectPolicy::KeepOrders(grace_period) => grace_period.max(RESTORED_GRACE_PERIOD),
+        };
+        let restored_traders =
+            self.engine.traders().map(|(trader, _account)| trader.clone()).collect::<Vec<_>>();
+        for trader in restored_traders {
+            self.execute(Command::DeregisterTrader { trader: trader.clone() });
+            self.cancel_orders_later(trader, grace_period, ctx);
+        }
 
-            let new_balance = Tick(dec!(1.50));
-            if let Some(obr) = act.order_book.get_mut(&new_balance) {
-                let new_balance = compress_order_book_range(obr);
-                act.update_listeners(messages::TickDataUpdate::NewLatestMatch(new_balance));
-            }
-            let update_msg = act.tick_data_refresh_msg();
-            act.update_listeners(update_msg);
-
-            for (_, trader) in act.traders.iter() {
-                let update_msg = messages::OrderStateUpdate {
-                    open_orders: trader.open_orders.clone(),
-                    matched_orders: trader.matched_orders.clone(),
-                };
-                trader.recp_order_update.do_send(update_msg);
-            }
-        });
+        // A restored market carries on in the status it was saved in
+        if self.engine.status() == MarketStatus::Inactive {
+            self.try_set_status(MarketStatus::Open);
+        }
+
+        ctx.run_interval(self.publish_interval, |act, _ctx| act.publish());
+
+        if let Some(snapshots) = self.snapshots.as_ref() {
+            ctx.run_interval(snapshots.interval, |act, _ctx| {
+                if let Err(err) = act.save_snapshot() {
+                    tracing::error!(err = ?err, "Failed to save the market snapshot");
+                }
+            });
+        }
+
+        if let Some(round_duration) = self.engine.config().round_duration {
+            ctx.run_interval(round_duration, |act, _ctx| {
+                act.try_set_status(MarketStatus::Closed);
+                let selections = &act.engine.config().selections;
+                if !selections.is_empty() {
+                    let winner = selections[rand::thread_rng().gen_range(0..selections.len())].id;
+                    if let Err(err) = act.execute(Command::Settle(winner)).into_transition() {
+                        tracing::warn!(err = ?err, "Failed to settle the market");
+                    }
+                }
+
+                // Start a new round with an empty order book
+                act.try_set_status(MarketStatus::Inactive);
+                act.try_set_status(MarketStatus::Open);
+            });
+        }
+    }
+}
+
+impl Handler<messages::SetMarketStatus> for MarketActor {
+    type Result = Result<(), messages::InvalidTransition>;
+
+    fn handle(&mut self, msg: messages::SetMarketStatus, _ctx: &mut Context<Self>) -> Self::Result {
+        tracing::info!(msg = ?msg, "Setting market status");
+        self.execute(Command::SetStatus(msg.0)).into_transition()
+    }
+}
+
+impl Handler<messages::SettleMarket> for MarketActor {
+    type Result = Result<(), messages::InvalidTransition>;
+
+    fn handle(&mut self, msg: messages::SettleMarket, _ctx: &mut Context<Self>) -> Self::Result {
+        tracing::info!(msg = ?msg, "Settling market");
+        self.execute(Command::Settle(msg.0)).into_transition()
     }
 }
 
 impl Handler<messages::PlaceOrder> for MarketActor {
-    type Result = ();
+    type Result = Result<messages::OrderPlaced, RejectReason>;
 
     fn handle(&mut self, msg: messages::PlaceOrder, _ctx: &mut Context<Self>) -> Self::Result {
         tracing::info!(msg = ?msg, "Received order");
-        let Some(trader) = self.traders.get_mut(&msg.trader) else {
-            return;
-        };
+        let messages::PlaceOrder { trader, request_id, order } = msg;
+        self.execute(Command::PlaceOrder { trader, request_id, order }).into_order()
+    }
+}
 
-        let tick = msg.order.tick;
-        if let Some(obr) = self.order_book.get_mut(&msg.order.tick) {
-            let affected_traders = match msg.order.side {
-                trading_types::common::Side::Back => Self::match_orders(
-                    msg,
-                    &mut obr.open_lays,
-                    &mut obr.total_matched,
-                    &mut obr.open_backs,
-                    trader,
-                ),
-                trading_types::common::Side::Lay => Self::match_orders(
-                    msg,
-                    &mut obr.open_backs,
-                    &mut obr.total_matched,
-                    &mut obr.open_lays,
-                    trader,
-                ),
-            };
-            // Send tick update to all listeners
-            let tick_data = compress_order_book_range(obr);
-            if !affected_traders.is_empty() {
-                self.update_listeners(messages::TickDataUpdate::NewLatestMatch(tick_data.clone()));
-            }
-            self.update_listeners(messages::TickDataUpdate::SingleUpdate(tick_data));
-
-            // Send individual order updates to affected traders
-            for (trader_id, new_size) in affected_traders {
-                let Some(trader) = self.traders.get_mut(&trader_id) else {
-                    continue;
-                };
-
-                if new_size.0 == dec!(0) {
-                    trader.open_orders.remove(&tick);
-                } else if let Some(order) = trader.open_orders.get_mut(&tick) {
-                    order.size = new_size;
-                }
+impl Handler<messages::CashOut> for MarketActor {
+    type Result = Result<messages::OrderPlaced, RejectReason>;
 
-                let update_msg = messages::OrderStateUpdate {
-                    open_orders: trader.open_orders.clone(),
-                    matched_orders: trader.matched_orders.clone(),
-                };
-                trader.recp_order_update.do_send(update_msg);
-            }
-        }
+    fn handle(&mut self, msg: messages::CashOut, _ctx: &mut Context<Self>) -> Self::Result {
+        tracing::info!(msg = ?msg, "Cashing out");
+        let messages::CashOut { trader, request_id, selection } = msg;
+        self.execute(Command::CashOut { trader, request_id, selection }).into_order()
     }
 }
 
-impl MarketActor {
-    fn match_orders(
-        order: PlaceOrder,
-        opposing_orders: &mut Vec<(TraderId, RequestId, Size)>,
-        matched_aggregate: &mut Size,
-        aligned_orders: &mut Vec<(TraderId, RequestId, Size)>,
-        trader: &mut InternalTraderState,
-    ) -> Vec<(TraderId, Size)> {
-        trader.open_orders.insert(order.order.tick, order.order.clone());
-
-        let mut affected_traders = vec![];
-        let mut leftover_amount = order.order.size;
-        let mut matched_amount = Size(dec!(0));
-
-        for (opposing_trader_id, _opposing_req_id, opposing_order_size) in
-            opposing_orders.iter_mut()
-        {
-            match leftover_amount.cmp(opposing_order_size) {
-                std::cmp::Ordering::Less => {
-                    opposing_order_size.0 -= leftover_amount.0;
-                    matched_amount.0 += leftover_amount.0;
-                    leftover_amount.0 = dec!(0);
-                }
-                std::cmp::Ordering::Equal => {
-                    matched_amount.0 += leftover_amount.0;
-                    opposing_order_size.0 = dec!(0);
-                    leftover_amount.0 = dec!(0);
-                }
-                std::cmp::Ordering::Greater => {
-                    leftover_amount.0 -= opposing_order_size.0;
-                    matched_amount.0 += opposing_order_size.0;
-                    opposing_order_size.0 = dec!(0);
-                }
-            }
-            affected_traders.push((opposing_trader_id.clone(), *opposing_order_size));
+impl Handler<messages::CancelOrder> for MarketActor {
+    type Result = ();
 
-            if order.order.size.0 == dec!(0) {
-                break
-            }
-        }
-        matched_aggregate.0 += matched_amount.0;
-
-        if leftover_amount.0 > dec!(0) {
-            aligned_orders.push((order.trader.clone(), order.request_id.clone(), leftover_amount));
-            if let Some(trader) = trader.open_orders.get_mut(&order.order.tick) {
-                trader.size = leftover_amount;
-            } else {
-                trader.open_orders.insert(
-                    order.order.tick,
-                    Order { size: leftover_amount, ..order.order.clone() },
-                );
-            }
+    fn handle(&mut self, msg: messages::CancelOrder, _ctx: &mut Context<Self>) -> Self::Result {
+        tracing::info!(msg = ?msg, "Cancelling order");
+        let messages::CancelOrder { trader, request_id } = msg;
+        self.execute(Command::CancelOrder { trader, request_id });
+    }
+}
+
+impl Handler<messages::CancelOrders> for MarketActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: messages::CancelOrders, _ctx: &mut Context<Self>) -> Self::Result {
+        tracing::info!(msg = ?msg, "Cancelling orders");
+        let messages::CancelOrders { trader, selection, tick } = msg;
+        self.execute(Command::CancelOrders { trader, selection, tick });
+    }
+}
+
+impl Handler<messages::RegisterTrader> for MarketActor {
+    type Result = Result<MarketDataReceiver, messages::AlreadyConnected>;
+
+    fn handle(&mut self, msg: messages::RegisterTrader, ctx: &mut Context<Self>) -> Self::Result {
+        tracing::info!(msg = ?msg, "Registering for market updates");
+        let messages::RegisterTrader(trader, recp_tick_update, recp_order_update) = msg;
+        if self.traders.contains_key(&trader) {
+            tracing::warn!(trader = ?trader, "Trader is already connected");
+            return Err(messages::AlreadyConnected)
         }
-        if matched_amount.0 > dec!(0) {
-            if let Some(trader) = trader.matched_orders.get_mut(&order.order.tick) {
-                trader.size.0 += matched_amount.0;
-            } else {
-                trader.matched_orders.insert(
-                    order.order.tick,
-                    Order { size: matched_amount, ..order.order.clone() },
-                );
-            }
+        // A trader that is back in time keeps its orders
+        if let Some(grace_period) = self.grace_periods.remove(&trader) {
+            ctx.cancel_future(grace_period);
         }
+        // Subscribe before the trader gets the market state, so that no update is missed
+        let receiver = MarketDataReceiver(self.market_data.subscribe());
+        self.traders.insert(trader.clone(), TraderListener { recp_tick_update, recp_order_update });
+        self.execute(Command::RegisterTrader { trader });
+        Ok(receiver)
+    }
+}
 
-        let update_msg = messages::OrderStateUpdate {
-            open_orders: trader.open_orders.clone(),
-            matched_orders: trader.matched_orders.clone(),
-        };
-        trader.recp_order_update.do_send(update_msg);
-
-        *opposing_orders =
-            opposing_orders.drain_filter(|(_, _, size)| size.0 > dec!(0)).collect::<Vec<_>>();
+impl Handler<messages::DeregisterTrader> for MarketActor {
+    type Result = ();
 
-        affected_traders
+    fn handle(&mut self, msg: messages::DeregisterTrader, ctx: &mut Context<Self>) -> Self::Result {
+        tracing::info!(msg = ?msg, "Deregistering from market updates");
+        let messages::DeregisterTrader(trader, recp_order_update) = msg;
+        // A connection that got refused as a duplicate deregisters as well
+        let current = self
+            .traders
+            .get(&trader)
+            .map_or(false, |listener| listener.recp_order_update == recp_order_update);
+        if !current {
+            return
+        }
+        self.traders.remove(&trader);
+        self.trader_left(trader, ctx);
     }
 }
 
-impl Handler<messages::RegisterTrader> for MarketActor {
+impl Handler<messages::RequestMarketState> for MarketActor {
     type Result = ();
 
-    fn handle(&mut self, msg: messages::RegisterTrader, _ctx: &mut Context<Self>) -> Self::Result {
-        tracing::info!(msg = ?msg, "Registering for market updates");
+    fn handle(
+        &mut self,
+        msg: messages::RequestMarketState,
+        _ctx: &mut Context<Self>,
+    ) -> Self::Result {
+        tracing::info!(msg = ?msg, "Sending market state");
+        if let Some(listener) = self.traders.get(&msg.0) {
+            self.send_market_state(listener);
+        }
+    }
+}
 
-        let update_msg = self.tick_data_refresh_msg();
-        msg.1.do_send(update_msg);
-        let state = InternalTraderState {
-            recp_tick_update: msg.1,
-            recp_order_update: msg.2,
-            open_orders: HashMap::new(),
-            matched_orders: HashMap::new(),
-        };
+impl Handler<messages::SaveSnapshot> for MarketActor {
+    type Result = std::io::Result<()>;
 
-        let update_msg = messages::OrderStateUpdate {
-            open_orders: state.open_orders.clone(),
-            matched_orders: state.matched_orders.clone(),
-        };
-        state.recp_order_update.do_send(update_msg);
-        self.traders.insert(msg.0, state);
+    fn handle(&mut self, msg: messages::SaveSnapshot, _ctx: &mut Context<Self>) -> Self::Result {
+        tracing::info!(msg = ?msg, "Saving market snapshot");
+        self.save_snapshot()
+    }
+}
+
+impl Handler<messages::GetCandles> for MarketActor {
+    type Result = Vec<Candle>;
+
+    fn handle(&mut self, msg: messages::GetCandles, _ctx: &mut Context<Self>) -> Self::Result {
+        self.engine.candles(msg.0)
     }
 }
 
@@ -262,69 +420,314 @@ impl Handler<messages::SpawnBot> for MarketActor {
     }
 }
 
-fn compress_order_book_range(value: &mut OrderBookRange) -> TickData {
-    let open_backs = value.open_backs.iter().map(|x| &x.2).fold(Size(dec!(0)), |acc, i| acc + i);
-    let open_lays = value.open_lays.iter().map(|x| &x.2).fold(Size(dec!(0)), |acc, i| acc + i);
-    let total_matched = value.total_matched;
-    TickData {
-        tick: value.tick,
-        total_matched,
-        available_backs: open_backs,
-        available_lays: open_lays,
+impl Default for MarketActor {
+    fn default() -> Self {
+        Self::new(MarketConfig::default())
     }
 }
 
 impl MarketActor {
-    pub fn new() -> Self {
-        let mut order_book = HashMap::new();
-        for tick in Tick::all() {
-            order_book.insert(tick, OrderBookRange::new(tick));
+    pub fn new(config: MarketConfig) -> Self {
+        Self::with_engine(MatchingEngine::new(config))
+    }
+
+    /// Continue a market from a snapshot written by an earlier run
+    pub fn restore(snapshot: MarketSnapshot) -> Self {
+        Self::with_engine(MatchingEngine::restore(snapshot))
+    }
+
+    fn with_engine(engine: MatchingEngine) -> Self {
+        Self {
+            publisher: LadderPublisher::new(engine.snapshot().sequence),
+            engine,
+            traders: HashMap::new(),
+            bots: Vec::new(),
+            journal: None,
+            snapshots: None,
+            publish_interval: PUBLISH_INTERVAL,
+            market_data: broadcast::channel(MARKET_DATA_CAPACITY).0,
+            grace_periods: HashMap::new(),
         }
+    }
 
-        Self { order_book, traders: HashMap::new(), bots: Vec::new() }
+    /// Send the ladder changes to the traders in one delta every `interval`
+    pub fn with_publish_interval(mut self, interval: Duration) -> Self {
+        self.publish_interval = interval;
+        self
     }
 
-    pub fn update_listeners(&self, msg: messages::TickDataUpdate) {
-        for (_, trader) in self.traders.iter() {
-            trader.recp_tick_update.do_send(msg.clone());
+    /// Write a snapshot of the market to the path every `interval`
+    pub fn with_snapshots(mut self, path: PathBuf, interval: Duration) -> Self {
+        self.snapshots = Some(Snapshots { path, interval });
+        self
+    }
+
+    fn save_snapshot(&self) -> std::io::Result<()> {
+        let Some(snapshots) = self.snapshots.as_ref() else {
+            return Ok(())
+        };
+        self.engine.market_snapshot().write(&snapshots.path)
+    }
+
+    /// Record every command and the events it caused in the journal
+    pub fn with_journal(mut self, mut journal: Journal) -> Self {
+        let started = JournalEntry::Started(Box::new(self.engine.market_snapshot()));
+        match journal.append(&started) {
+            Ok(()) => self.journal = Some(journal),
+            Err(err) => tracing::error!(err = ?err, "Failed to write to the journal"),
+        }
+        self
+    }
+
+    /// Run the command on the engine, journal it and send its events to the traders
+    fn execute(&mut self, command: Command) -> Outcome {
+        let now = Utc::now();
+        let (outcome, events) = self.engine.execute(command.clone(), now);
+        self.journal(now, command, &events);
+        self.fan_out(events);
+        outcome
+    }
+
+    fn journal(&mut self, at: DateTime<Utc>, command: Command, events: &[Event]) {
+        let Some(journal) = self.journal.as_mut() else {
+            return;
+        };
+        let entries = std::iter::once(JournalEntry::Command { at, command })
+            .chain(events.iter().cloned().map(JournalEntry::Event));
+        for entry in entries {
+            if let Err(err) = journal.append(&entry) {
+                tracing::error!(err = ?err, "Failed to write to the journal, disabling it");
+                self.journal = None;
+                return
+            }
+        }
+    }
+
+    /// Send every trader the updates caused by the events
+    fn fan_out(&mut self, events: Vec<Event>) {
+        let mut changed_traders = vec![];
+        for event in events {
+            match event {
+                Event::TraderRegistered { trader } => {
+                    if let Some(listener) = self.traders.get(&trader) {
+                        self.send_market_state(listener);
+                    }
+                    changed_traders.push(trader);
+                }
+                Event::OrderAccepted { trader, .. } | Event::OrderCancelled { trader, .. } => {
+                    changed_traders.push(trader)
+                }
+                Event::OrderRejected { .. } => {}
+                Event::TraderRemoved { trader } => {
+                    tracing::info!(trader = ?trader, "Trader removed");
+                }
+                Event::OrderFilled { trader, request_id, price, matched, remaining, aggressor } => {
+                    if let (false, Some(listener)) = (aggressor, self.traders.get(&trader)) {
+                        let fill = messages::OrderFill { request_id, price, matched, remaining };
+                        listener
+                            .recp_order_update
+                            .do_send(messages::TraderUpdate::OrderFilled(fill));
+                    }
+                    changed_traders.push(trader);
+                }
+                Event::SelfTradePrevented { trader, prevented } => {
+                    if let Some(listener) = self.traders.get(&trader) {
+                        listener
+                            .recp_order_update
+                            .do_send(messages::TraderUpdate::SelfTradePrevented(prevented));
+                    }
+                    changed_traders.push(trader);
+                }
+                Event::TickUpdated { sequence, tick_data } => {
+                    self.publisher.tick_updated(sequence, tick_data)
+                }
+                Event::LatestMatch { sequence, tick_data } => {
+                    self.publisher.latest_match(sequence, tick_data)
+                }
+                Event::Traded(trade) => {
+                    self.update_listeners(messages::TickDataUpdate::Trade(trade))
+                }
+                Event::BookReset => {
+                    let snapshot = self.engine.snapshot();
+                    self.publisher.reset(snapshot.sequence);
+                    self.update_listeners(messages::TickDataUpdate::SetRefresh(snapshot));
+                    self.update_listeners(messages::TickDataUpdate::TradeHistory(vec![]));
+                    for (trader_id, listener) in self.traders.iter() {
+                        self.send_trader_state(trader_id);
+                        listener
+                            .recp_order_update
+                            .do_send(messages::TraderUpdate::Positions(vec![]));
+                    }
+                }
+                Event::StatusChanged(status) => {
+                    // The ladder the traders see has to be final before the status changes
+                    self.publish();
+                    self.update_listeners(messages::TickDataUpdate::MarketStatus(status))
+                }
+                Event::TraderSettled { trader, winner, profit_and_loss, commission, balance } => {
+                    tracing::info!(
+                        trader = ?trader,
+                        profit_and_loss = ?profit_and_loss,
+                        commission = ?commission,
+                        "Trader settled"
+                    );
+                    if let Some(listener) = self.traders.get(&trader) {
+                        let settlement =
+                            Settlement { winner, profit_and_loss, commission, balance };
+                        listener
+                            .recp_order_update
+                            .do_send(messages::TraderUpdate::Settlement(settlement));
+                    }
+                    changed_traders.push(trader);
+                }
+            }
+        }
+
+        changed_traders.sort();
+        changed_traders.dedup();
+        for trader_id in changed_traders.iter() {
+            self.send_trader_state(trader_id);
+        }
+    }
+
+    /// Send the ladder changes collected since the last delta, together with fresh positions on
+    /// the selections they were on
+    fn publish(&mut self) {
+        let Some(delta) = self.publisher.take_delta() else {
+            return
+        };
+        let mut changed_selections: Vec<_> =
+            delta.ticks.iter().map(|tick_data| tick_data.selection).collect();
+        changed_selections.dedup();
+        self.update_listeners(messages::TickDataUpdate::LadderDelta(delta));
+        for selection in changed_selections {
+            self.send_positions(selection);
         }
     }
 
-    fn tick_data_refresh_msg(&mut self) -> messages::TickDataUpdate {
-        let mut tick_data =
-            self.order_book.values_mut().map(compress_order_book_range).collect::<Vec<_>>();
-        tick_data.sort_by(|a, b| match a.tick.0 < b.tick.0 {
-            true => std::cmp::Ordering::Less,
-            false => std::cmp::Ordering::Greater,
+    /// Everything a newly registered trader needs to show the market
+    fn send_market_state(&self, listener: &TraderListener) {
+        let config = self.engine.config();
+        let recp_tick_update = &listener.recp_tick_update;
+        recp_tick_update.do_send(messages::TickDataUpdate::Selections(config.selections.clone()));
+        recp_tick_update.do_send(messages::TickDataUpdate::SetRefresh(self.engine.snapshot()));
+        recp_tick_update.do_send(messages::TickDataUpdate::TradeHistory(self.engine.trades()));
+        recp_tick_update.do_send(messages::TickDataUpdate::MarketStatus(self.engine.status()));
+    }
+
+    /// Deal with the orders of a trader that is no longer connected according to the
+    /// [`DisconnectPolicy`]
+    fn trader_left(&mut self, trader: TraderId, ctx: &mut Context<Self>) {
+        self.execute(Command::DeregisterTrader { trader: trader.clone() });
+        match self.engine.config().disconnect_policy {
+            DisconnectPolicy::CancelOrders => self.cancel_all_orders(trader),
+            DisconnectPolicy::KeepOrders(grace_period) => {
+                self.cancel_orders_later(trader, grace_period, ctx)
+            }
+        }
+    }
+
+    /// Cancel the orders of the trader unless it registers again within the grace period
+    fn cancel_orders_later(
+        &mut self,
+        trader: TraderId,
+        grace_period: Duration,
+        ctx: &mut Context<Self>,
+    ) {
+        let handle = ctx.run_later(grace_period, {
+            let trader = trader.clone();
+            move |act, _ctx| {
+                act.grace_periods.remove(&trader);
+                act.cancel_all_orders(trader);
+            }
         });
+        if let Some(previous) = self.grace_periods.insert(trader, handle) {
+            ctx.cancel_future(previous);
+        }
+    }
 
-        messages::TickDataUpdate::SetRefresh(tick_data)
+    fn cancel_all_orders(&mut self, trader: TraderId) {
+        self.execute(Command::CancelOrders { trader, selection: None, tick: None });
     }
-}
 
-#[derive(Message)]
-#[rtype(result = "()")]
-struct OrderBookRange {
-    open_backs: Vec<(TraderId, RequestId, Size)>,
-    open_lays: Vec<(TraderId, RequestId, Size)>,
-    total_matched: Size,
-    tick: Tick,
-}
+    fn try_set_status(&mut self, status: MarketStatus) {
+        if let Err(err) = self.execute(Command::SetStatus(status)).into_transition() {
+            tracing::warn!(err = ?err, "Invalid market status transition");
+        }
+    }
 
-impl OrderBookRange {
-    fn new(tick: Tick) -> Self {
-        Self { open_backs: Vec::new(), open_lays: Vec::new(), tick, total_matched: Size(dec!(0)) }
+    /// Encode the update once and send it to every trader
+    pub fn update_listeners(&self, msg: messages::TickDataUpdate) {
+        let Some(data) = MarketData::new(msg) else {
+            return
+        };
+        // Fails only when nobody is watching the market
+        let _ = self.market_data.send(Arc::new(data));
     }
 
-    fn clear(&mut self) {
-        self.open_backs.clear();
-        self.open_lays.clear();
-        self.total_matched = Size(dec!(0));
+    /// Send the trader their orders, balance and exposure
+    fn send_trader_state(&self, trader_id: &TraderId) {
+        let (Some(listener), Some((orders, info))) =
+            (self.traders.get(trader_id), self.engine.trader_state(trader_id))
+        else {
+            return;
+        };
+        listener.recp_order_update.do_send(messages::TraderUpdate::OrderState(orders));
+        listener.recp_order_update.do_send(messages::TraderUpdate::TraderInfo(info));
+    }
+
+    /// Send fresh positions to every trader with matched orders on the selection, as its prices
+    /// might have moved
+    fn send_positions(&self, selection: SelectionId) {
+        for (trader_id, listener) in self.traders.iter() {
+            if !self.engine.has_position(trader_id, selection) {
+                continue
+            }
+            let positions = self.engine.positions(trader_id);
+            listener.recp_order_update.do_send(messages::TraderUpdate::Positions(positions));
+        }
     }
 }
-impl InternalTraderState {
-    fn clear(&mut self) {
-        self.matched_orders.clear();
-        self.open_orders.clear();
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal_macros::dec;
+    use trading_types::common::{Side, Size};
+
+    use super::*;
+
+    #[actix::test]
+    async fn restored_orders_outlive_the_restart() {
+        let mut engine = MatchingEngine::new(MarketConfig {
+            round_duration: None,
+            disconnect_policy: DisconnectPolicy::CancelOrders,
+            ..MarketConfig::default()
+        });
+        let trader = TraderId("restored".to_string());
+        let request_id = RequestId("resting".to_string());
+        let order = Order {
+            selection: SelectionId(1),
+            tick: Tick(dec!(2)),
+            size: Size(dec!(10)),
+            side: Side::Back,
+        };
+        let now = Utc::now();
+        engine.execute(Command::RegisterTrader { trader: trader.clone() }, now);
+        engine.execute(Command::SetStatus(MarketStatus::Open), now);
+        let command =
+            Command::PlaceOrder { trader: trader.clone(), request_id: request_id.clone(), order };
+        engine.execute(command, now).0.into_order().unwrap();
+
+        let path =
+            std::env::temp_dir().join(format!("restored-market-{}.cbor", std::process::id()));
+        let market = MarketActor::restore(engine.market_snapshot())
+            .with_snapshots(path.clone(), Duration::from_secs(3600))
+            .start();
+        market.send(messages::SaveSnapshot).await.unwrap().unwrap();
+        let snapshot = MarketSnapshot::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let record = &snapshot.traders[&trader].orders[&request_id];
+        assert_eq!(record.remaining_size, Size(dec!(10)));
     }
 }
@@ -0,0 +1,270 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
+use trading_types::common::{RequestId, SelectionId, Side, Size, Tick, TraderId};
+use trading_types::from_server::TickData;
+
+/// An order waiting in the book to be matched
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "(TraderId, RequestId, Size)", into = "(TraderId, RequestId, Size)")]
+pub(crate) struct RestingOrder {
+    pub trader: TraderId,
+    pub request_id: RequestId,
+    pub size: Size,
+}
+
+impl From<(TraderId, RequestId, Size)> for RestingOrder {
+    fn from((trader, request_id, size): (TraderId, RequestId, Size)) -> Self {
+        Self { trader, request_id, size }
+    }
+}
+
+impl From<RestingOrder> for (TraderId, RequestId, Size) {
+    fn from(order: RestingOrder) -> Self {
+        (order.trader, order.request_id, order.size)
+    }
+}
+
+/// Resting orders of one side of a tick in time priority, together with their total size
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "VecDeque<RestingOrder>", into = "VecDeque<RestingOrder>")]
+pub(crate) struct OrderQueue {
+    orders: VecDeque<RestingOrder>,
+    size: Size,
+}
+
+impl Default for OrderQueue {
+    fn default() -> Self {
+        Self { orders: VecDeque::new(), size: Size(dec!(0)) }
+    }
+}
+
+impl From<VecDeque<RestingOrder>> for OrderQueue {
+    fn from(orders: VecDeque<RestingOrder>) -> Self {
+        let size = orders.iter().fold(Size(dec!(0)), |acc, order| acc + &order.size);
+        Self { orders, size }
+    }
+}
+
+impl From<OrderQueue> for VecDeque<RestingOrder> {
+    fn from(queue: OrderQueue) -> Self {
+        queue.orders
+    }
+}
+
+impl OrderQueue {
+    pub fn is_empty(&self) -> bool {
+        self.orders.is_empty()
+    }
+
+    /// Total size of the queued orders
+    pub fn size(&self) -> Size {
+        self.size
+    }
+
+    pub fn get(&self, index: usize) -> Option<&RestingOrder> {
+        self.orders.get(index)
+    }
+
+    pub fn push_back(&mut self, order: RestingOrder) {
+        self.size.0 += order.size.0;
+        self.orders.push_back(order);
+    }
+
+    /// Take `size` off the order at `index`, removing it once nothing is left. Returns whether the
+    /// order got removed.
+    pub fn reduce(&mut self, index: usize, size: Size) -> bool {
+        let Some(order) = self.orders.get_mut(index) else {
+            return false
+        };
+        order.size.0 -= size.0;
+        self.size.0 -= size.0;
+        if order.size.0 > dec!(0) {
+            return false
+        }
+        self.orders.remove(index);
+        true
+    }
+
+    /// Remove the orders matching the predicate, returning the size removed per order
+    pub fn remove_where(
+        &mut self,
+        predicate: impl Fn(&TraderId, &RequestId) -> bool,
+    ) -> Vec<(RequestId, Size)> {
+        let mut removed = vec![];
+        let mut size = Size(dec!(0));
+        self.orders.retain(|order| {
+            if predicate(&order.trader, &order.request_id) {
+                removed.push((order.request_id.clone(), order.size));
+                return false
+            }
+            size.0 += order.size.0;
+            true
+        });
+        self.size = size;
+        removed
+    }
+
+    pub fn clear(&mut self) {
+        self.orders.clear();
+        self.size = Size(dec!(0));
+    }
+}
+
+/// All orders resting at a single tick of a selection
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct OrderBookRange {
+    pub open_backs: OrderQueue,
+    pub open_lays: OrderQueue,
+    pub total_matched: Size,
+    pub selection: SelectionId,
+    pub tick: Tick,
+}
+
+impl OrderBookRange {
+    fn new(selection: SelectionId, tick: Tick) -> Self {
+        Self {
+            open_backs: OrderQueue::default(),
+            open_lays: OrderQueue::default(),
+            selection,
+            tick,
+            total_matched: Size(dec!(0)),
+        }
+    }
+
+    fn clear(&mut self) {
+        self.open_backs.clear();
+        self.open_lays.clear();
+        self.total_matched = Size(dec!(0));
+    }
+
+    /// Neither resting orders nor matched volume
+    pub fn is_empty(&self) -> bool {
+        self.open_backs.is_empty() && self.open_lays.is_empty() && self.total_matched.0 == dec!(0)
+    }
+
+    /// Remove the resting orders matching the predicate, returning the size removed per order
+    fn cancel(
+        &mut self,
+        predicate: impl Fn(&TraderId, &RequestId) -> bool,
+    ) -> Vec<(RequestId, Size)> {
+        let mut cancelled = self.open_backs.remove_where(&predicate);
+        cancelled.extend(self.open_lays.remove_where(&predicate));
+        cancelled
+    }
+
+    pub fn tick_data(&self) -> TickData {
+        TickData {
+            selection: self.selection,
+            tick: self.tick,
+            total_matched: self.total_matched,
+            available_backs: self.open_backs.size(),
+            available_lays: self.open_lays.size(),
+        }
+    }
+}
+
+/// The ladder of a single selection
+#[derive(Debug, Clone)]
+pub(crate) struct OrderBook {
+    levels: BTreeMap<Tick, OrderBookRange>,
+    /// Ticks with resting backs, kept so that the best prices are found without walking the
+    /// whole ladder
+    back_ticks: BTreeSet<Tick>,
+    /// Ticks with resting lays
+    lay_ticks: BTreeSet<Tick>,
+}
+
+impl OrderBook {
+    pub fn new(selection: SelectionId) -> Self {
+        let levels = Tick::all()
+            .into_iter()
+            .map(|tick| (tick, OrderBookRange::new(selection, tick)))
+            .collect();
+        Self { levels, back_ticks: BTreeSet::new(), lay_ticks: BTreeSet::new() }
+    }
+
+    pub fn contains(&self, tick: &Tick) -> bool {
+        self.levels.contains_key(tick)
+    }
+
+    pub fn level(&self, tick: &Tick) -> Option<&OrderBookRange> {
+        self.levels.get(tick)
+    }
+
+    /// Every tick of the ladder from the lowest
+    pub fn levels(&self) -> impl Iterator<Item = &OrderBookRange> {
+        self.levels.values()
+    }
+
+    /// Mutable access to a tick, [`OrderBook::refresh`] has to be called once it's done
+    pub fn level_mut(&mut self, tick: &Tick) -> Option<&mut OrderBookRange> {
+        self.levels.get_mut(tick)
+    }
+
+    /// Bring the price index up to date with the orders resting at the tick
+    pub fn refresh(&mut self, tick: Tick) {
+        let Some(level) = self.levels.get(&tick) else {
+            return;
+        };
+        for (ticks, queue) in
+            [(&mut self.back_ticks, &level.open_backs), (&mut self.lay_ticks, &level.open_lays)]
+        {
+            if queue.is_empty() {
+                ticks.remove(&tick);
+            } else {
+                ticks.insert(tick);
+            }
+        }
+    }
+
+    /// Replace a tick with a saved one
+    pub fn restore_level(&mut self, level: OrderBookRange) {
+        let tick = level.tick;
+        if let Some(current) = self.levels.get_mut(&tick) {
+            *current = level;
+            self.refresh(tick);
+        }
+    }
+
+    /// Ticks that an incoming order on the side and at the tick would match at, best price first.
+    /// A back matches lays at its tick or above, a lay matches backs at its tick or below.
+    pub fn matchable_ticks(&self, side: Side, tick: Tick) -> Vec<Tick> {
+        match side {
+            Side::Back => self.lay_ticks.range(tick..).rev().copied().collect(),
+            Side::Lay => self.back_ticks.range(..=tick).copied().collect(),
+        }
+    }
+
+    /// Highest tick with lays waiting to be matched
+    pub fn best_back(&self) -> Option<Tick> {
+        self.lay_ticks.last().copied()
+    }
+
+    /// Lowest tick with backs waiting to be matched
+    pub fn best_lay(&self) -> Option<Tick> {
+        self.back_ticks.first().copied()
+    }
+
+    pub fn cancel(
+        &mut self,
+        tick: Tick,
+        predicate: impl Fn(&TraderId, &RequestId) -> bool,
+    ) -> Vec<(RequestId, Size)> {
+        let Some(level) = self.levels.get_mut(&tick) else {
+            return vec![]
+        };
+        let cancelled = level.cancel(predicate);
+        self.refresh(tick);
+        cancelled
+    }
+
+    pub fn clear(&mut self) {
+        for level in self.levels.values_mut() {
+            level.clear();
+        }
+        self.back_ticks.clear();
+        self.lay_ticks.clear();
+    }
+}
@@ -0,0 +1,134 @@
+use rust_decimal::Decimal;
+use trading_types::common::{OrderRecord, SelectionId, Side, Size, Tick};
+use trading_types::from_server::{Hedge, Position};
+
+use crate::validation::OrderRules;
+
+/// Best prices an order placed now would be matched at
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BestPrices {
+    /// Highest tick with lays waiting to be matched
+    pub back: Option<Tick>,
+    /// Lowest tick with backs waiting to be matched
+    pub lay: Option<Tick>,
+}
+
+/// Position of the matched orders on the selection, `None` if nothing got matched
+pub fn position<'a>(
+    orders: impl IntoIterator<Item = &'a OrderRecord>,
+    selection: SelectionId,
+    best_prices: BestPrices,
+    rules: &OrderRules,
+) -> Option<Position> {
+    let (mut back_stake, mut back_winnings) = (Decimal::ZERO, Decimal::ZERO);
+    let (mut lay_stake, mut lay_liability) = (Decimal::ZERO, Decimal::ZERO);
+    let fills = orders
+        .into_iter()
+        .filter(|order| order.selection == selection)
+        .flat_map(|order| order.fills.iter().map(move |fill| (order.side, fill)));
+    for (side, fill) in fills {
+        let winnings = fill.size.0 * (fill.price.0 - Decimal::ONE);
+        match side {
+            Side::Back => {
+                back_stake += fill.size.0;
+                back_winnings += winnings;
+            }
+            Side::Lay => {
+                lay_stake += fill.size.0;
+                lay_liability += winnings;
+            }
+        }
+    }
+    if back_stake.is_zero() && lay_stake.is_zero() {
+        return None
+    }
+
+    let average_price = |stake: Decimal, winnings: Decimal| {
+        (!stake.is_zero()).then(|| Decimal::ONE + winnings / stake)
+    };
+    let profit_if_wins = back_winnings - lay_liability;
+    let profit_if_loses = lay_stake - back_stake;
+    Some(Position {
+        selection,
+        back_stake: Size(back_stake),
+        lay_liability: Size(lay_liability),
+        average_back_price: average_price(back_stake, back_winnings),
+        average_lay_price: average_price(lay_stake, lay_liability),
+        profit_if_wins,
+        profit_if_loses,
+        cash_out: hedge(profit_if_wins, profit_if_loses, best_prices, rules),
+    })
+}
+
+/// A hedge of size `h` at price `p` moves `h * (p - 1)` between the outcomes in one direction and
+/// `h` in the other, so `h = (if_wins - if_loses) / p` makes both outcomes pay the same. The hedge
+/// is capped at the maximum stake, and there is none below the minimum stake as it could not be
+/// placed.
+fn hedge(
+    profit_if_wins: Decimal,
+    profit_if_loses: Decimal,
+    best_prices: BestPrices,
+    rules: &OrderRules,
+) -> Option<Hedge> {
+    let difference = profit_if_wins - profit_if_loses;
+    let (side, price) = match difference {
+        difference if difference > Decimal::ZERO => (Side::Lay, best_prices.lay?),
+        difference if difference < Decimal::ZERO => (Side::Back, best_prices.back?),
+        _ => return None,
+    };
+    let size = (difference.abs() / price.0).round_dp(rules.stake_precision).min(rules.max_stake.0);
+    if size < rules.min_stake.0 || size.is_zero() {
+        return None
+    }
+    let winnings = size * (price.0 - Decimal::ONE);
+    let (if_wins, if_loses) = match side {
+        Side::Lay => (profit_if_wins - winnings, profit_if_loses + size),
+        Side::Back => (profit_if_wins + winnings, profit_if_loses - size),
+    };
+    Some(Hedge { side, price, size: Size(size), profit: if_wins.min(if_loses) })
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal_macros::dec;
+
+    use super::*;
+    use crate::testing::check_cases;
+
+    #[test]
+    fn hedge_equalises_both_outcomes() {
+        let hedge_of = |side, price, size, profit| Hedge {
+            side,
+            price: Tick(price),
+            size: Size(size),
+            profit,
+        };
+        let cases = [
+            // Backed 1000 at 2.0, laying (1000 + 1000) / 1.5 locks in the same profit either way
+            (
+                (dec!(1000), dec!(-1000)),
+                Some(hedge_of(Side::Lay, dec!(1.5), dec!(1333.33), dec!(333.33))),
+            ),
+            // Laid 100 at 3.0
+            ((dec!(-200), dec!(100)), Some(hedge_of(Side::Back, dec!(2), dec!(150), dec!(-50)))),
+            ((dec!(50), dec!(50)), None),
+            // Below the minimum stake
+            ((dec!(1), dec!(0)), None),
+            // Capped at the maximum stake
+            (
+                (dec!(30000), dec!(0)),
+                Some(hedge_of(Side::Lay, dec!(1.5), dec!(10000), dec!(10000))),
+            ),
+        ];
+        let best_prices = BestPrices { back: Some(Tick(dec!(2))), lay: Some(Tick(dec!(1.5))) };
+        check_cases(cases, |(profit_if_wins, profit_if_loses)| {
+            hedge(*profit_if_wins, *profit_if_loses, best_prices, &OrderRules::default())
+        });
+    }
+
+    #[test]
+    fn hedge_needs_a_price() {
+        let hedge = hedge(dec!(100), dec!(0), BestPrices::default(), &OrderRules::default());
+        assert_eq!(hedge, None);
+    }
+}
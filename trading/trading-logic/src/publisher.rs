@@ -0,0 +1,122 @@
+use std::collections::BTreeMap;
+
+use trading_types::common::{SelectionId, Tick};
+use trading_types::from_server::{LadderDelta, TickData};
+
+/// Collects the ladder changes of a market between two publications, keeping only the latest
+/// state of every changed tick
+#[derive(Debug)]
+pub(crate) struct LadderPublisher {
+    /// Sequence number the last delta or snapshot sent to the traders ended at
+    published: u64,
+    sequence: u64,
+    ticks: BTreeMap<(SelectionId, Tick), TickData>,
+    latest_matches: BTreeMap<SelectionId, TickData>,
+}
+
+impl LadderPublisher {
+    pub fn new(sequence: u64) -> Self {
+        Self {
+            published: sequence,
+            sequence,
+            ticks: BTreeMap::new(),
+            latest_matches: BTreeMap::new(),
+        }
+    }
+
+    pub fn tick_updated(&mut self, sequence: u64, tick_data: TickData) {
+        if sequence <= self.published {
+            return
+        }
+        self.sequence = self.sequence.max(sequence);
+        self.ticks.insert((tick_data.selection, tick_data.tick), tick_data);
+    }
+
+    pub fn latest_match(&mut self, sequence: u64, tick_data: TickData) {
+        if sequence <= self.published {
+            return
+        }
+        self.sequence = self.sequence.max(sequence);
+        self.latest_matches.insert(tick_data.selection, tick_data);
+    }
+
+    /// Drop the pending changes, as every trader gets a snapshot at `sequence` instead
+    pub fn reset(&mut self, sequence: u64) {
+        self.published = sequence;
+        self.sequence = sequence;
+        self.ticks.clear();
+        self.latest_matches.clear();
+    }
+
+    /// Everything that changed since the last delta, if anything did
+    pub fn take_delta(&mut self) -> Option<LadderDelta> {
+        if self.sequence == self.published {
+            return None
+        }
+        let delta = LadderDelta {
+            previous: self.published,
+            sequence: self.sequence,
+            ticks: std::mem::take(&mut self.ticks).into_values().collect(),
+            latest_matches: std::mem::take(&mut self.latest_matches).into_values().collect(),
+        };
+        self.published = self.sequence;
+        Some(delta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal_macros::dec;
+    use trading_types::common::Size;
+
+    use super::*;
+
+    fn tick_data(tick: Tick, available_backs: Size) -> TickData {
+        TickData {
+            total_matched: Size(dec!(0)),
+            available_backs,
+            available_lays: Size(dec!(0)),
+            selection: SelectionId(1),
+            tick,
+        }
+    }
+
+    #[test]
+    fn deltas_continue_from_the_previous_one() {
+        let mut publisher = LadderPublisher::new(3);
+        assert!(publisher.take_delta().is_none());
+
+        publisher.tick_updated(4, tick_data(Tick(dec!(2)), Size(dec!(10))));
+        publisher.tick_updated(5, tick_data(Tick(dec!(2)), Size(dec!(20))));
+        publisher.latest_match(6, tick_data(Tick(dec!(3)), Size(dec!(5))));
+        let delta = publisher.take_delta().unwrap();
+        assert_eq!((delta.previous, delta.sequence), (3, 6));
+        // Only the latest state of the tick
+        assert_eq!(delta.ticks, vec![tick_data(Tick(dec!(2)), Size(dec!(20)))]);
+        assert_eq!(delta.latest_matches, vec![tick_data(Tick(dec!(3)), Size(dec!(5)))]);
+        assert!(publisher.take_delta().is_none());
+
+        publisher.tick_updated(7, tick_data(Tick(dec!(2)), Size(dec!(0))));
+        let delta = publisher.take_delta().unwrap();
+        assert_eq!((delta.previous, delta.sequence), (6, 7));
+    }
+
+    #[test]
+    fn reset_drops_changes_covered_by_the_snapshot() {
+        let mut publisher = LadderPublisher::new(0);
+        publisher.tick_updated(1, tick_data(Tick(dec!(2)), Size(dec!(10))));
+        publisher.latest_match(2, tick_data(Tick(dec!(2)), Size(dec!(10))));
+        publisher.reset(5);
+        assert!(publisher.take_delta().is_none());
+
+        // Updates the snapshot already contains are ignored
+        publisher.tick_updated(5, tick_data(Tick(dec!(3)), Size(dec!(10))));
+        assert!(publisher.take_delta().is_none());
+
+        publisher.tick_updated(6, tick_data(Tick(dec!(4)), Size(dec!(10))));
+        let delta = publisher.take_delta().unwrap();
+        assert_eq!((delta.previous, delta.sequence), (5, 6));
+        assert_eq!(delta.ticks, vec![tick_data(Tick(dec!(4)), Size(dec!(10)))]);
+        assert!(delta.latest_matches.is_empty());
+    }
+}
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
+use trading_types::common::{Fill, OrderRecord, Outcome, SelectionId, Side, TraderId};
+
+/// Commission charged on the net winnings of a trader when the market settles
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Commission {
+    pub rate: Decimal,
+    /// Fraction of the commission that is waived for the trader
+    pub discounts: HashMap<TraderId, Decimal>,
+}
+
+impl Default for Commission {
+    fn default() -> Self {
+        Self { rate: dec!(0.05), discounts: HashMap::new() }
+    }
+}
+
+impl Commission {
+    /// Commission on the profit of the market, nothing is charged on a loss
+    pub fn charge(&self, trader: &TraderId, profit_and_loss: Decimal) -> Decimal {
+        if profit_and_loss <= Decimal::ZERO {
+            return Decimal::ZERO
+        }
+        let discount = self.discounts.get(trader).copied().unwrap_or_default();
+        let rate = self.rate * (Decimal::ONE - discount.clamp(Decimal::ZERO, Decimal::ONE));
+        (profit_and_loss * rate).round_dp(2)
+    }
+}
+
+/// Profit or loss of the matched part of the orders, given which selection won the market
+pub fn profit_and_loss<'a>(
+    orders: impl IntoIterator<Item = &'a OrderRecord>,
+    winner: SelectionId,
+) -> Decimal {
+    orders
+        .into_iter()
+        .flat_map(|order| {
+            let outcome = if order.selection == winner { Outcome::Won } else { Outcome::Lost };
+            order.fills.iter().map(move |fill| fill_profit(order.side, fill, outcome))
+        })
+        .sum()
+}
+
+fn fill_profit(side: Side, fill: &Fill, outcome: Outcome) -> Decimal {
+    let winnings = fill.size.0 * (fill.price.0 - Decimal::ONE);
+    match (side, outcome) {
+        (Side::Back, Outcome::Won) => winnings,
+        (Side::Back, Outcome::Lost) => -fill.size.0,
+        (Side::Lay, Outcome::Won) => -winnings,
+        (Side::Lay, Outcome::Lost) => fill.size.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{back, check_cases, lay, OrderBuilder};
+
+    #[test]
+    fn profit_and_loss_of_matched_orders() {
+        let cases = [
+            ((vec![back(1, dec!(2.5)).matched(dec!(100))], 1), dec!(150)),
+            ((vec![back(1, dec!(2.5)).matched(dec!(100))], 2), dec!(-100)),
+            ((vec![lay(1, dec!(2.5)).matched(dec!(100))], 1), dec!(-150)),
+            ((vec![lay(1, dec!(2.5)).matched(dec!(100))], 2), dec!(100)),
+            // Every fill pays out at its own price
+            (
+                (vec![back(1, dec!(2)).matched(dec!(50)).matched_at(dec!(3), dec!(50))], 1),
+                dec!(150),
+            ),
+            ((vec![back(1, dec!(2)).unmatched(dec!(100))], 1), dec!(0)),
+            (
+                (vec![back(1, dec!(3)).matched(dec!(100)), lay(1, dec!(2)).matched(dec!(100))], 1),
+                dec!(100),
+            ),
+            (
+                (vec![back(1, dec!(3)).matched(dec!(100)), lay(1, dec!(2)).matched(dec!(100))], 2),
+                dec!(0),
+            ),
+            (
+                (vec![back(1, dec!(1.5)).matched(dec!(10)), back(2, dec!(4)).matched(dec!(10))], 2),
+                dec!(20),
+            ),
+        ];
+        check_cases(cases, |(orders, winner)| {
+            profit_and_loss(orders.iter().map(OrderBuilder::record), SelectionId(*winner))
+        });
+    }
+
+    #[test]
+    fn commission_on_winnings() {
+        let discounted = |discount: Decimal| Commission {
+            rate: dec!(0.05),
+            discounts: HashMap::from([(TraderId("discounted".to_string()), discount)]),
+        };
+        let cases = [
+            ((Commission::default(), "trader", dec!(100)), dec!(5)),
+            ((Commission::default(), "trader", dec!(0)), dec!(0)),
+            ((Commission::default(), "trader", dec!(-100)), dec!(0)),
+            ((Commission::default(), "trader", dec!(33.33)), dec!(1.67)),
+            // Halves round to even
+            ((Commission::default(), "trader", dec!(10.1)), dec!(0.50)),
+            ((discounted(dec!(0.5)), "discounted", dec!(100)), dec!(2.5)),
+            ((discounted(dec!(0.5)), "discounted", dec!(-100)), dec!(0)),
+            ((discounted(dec!(0.5)), "trader", dec!(100)), dec!(5)),
+            ((discounted(dec!(1.5)), "discounted", dec!(100)), dec!(0)),
+            ((discounted(dec!(-0.5)), "discounted", dec!(100)), dec!(5)),
+        ];
+        check_cases(cases, |(commission, trader, profit_and_loss)| {
+            commission.charge(&TraderId(trader.to_string()), *profit_and_loss)
+        });
+    }
+}
@@ -0,0 +1,47 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use trading_types::common::{MarketStatus, SelectionId, Tick, TraderId};
+use trading_types::from_server::Trade;
+
+use crate::engine::{MarketConfig, TraderAccount};
+use crate::order_book::OrderBookRange;
+
+/// Everything needed to bring a market back after a restart
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MarketSnapshot {
+    pub config: MarketConfig,
+    pub status: MarketStatus,
+    /// Sequence number of the latest ladder update
+    pub sequence: u64,
+    pub next_trade_sequence: u64,
+    pub latest_matches: BTreeMap<SelectionId, Tick>,
+    /// Orders and balances, the positions are derived from the order fills
+    pub traders: BTreeMap<TraderId, TraderAccount>,
+    pub trades: Vec<Trade>,
+    /// Ticks with resting orders or matched volume
+    pub(crate) levels: Vec<OrderBookRange>,
+}
+
+impl MarketSnapshot {
+    /// Write the snapshot as CBOR. The file is replaced only once the new one is complete, so a
+    /// crash never leaves a half written snapshot behind.
+    pub fn write(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let tmp_path = path.with_extension("tmp");
+        let mut writer = BufWriter::new(File::create(&tmp_path)?);
+        ciborium::into_writer(self, &mut writer)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+        writer.into_inner()?.sync_all()?;
+        std::fs::rename(tmp_path, path)
+    }
+
+    pub fn read(path: impl AsRef<Path>) -> io::Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+        ciborium::from_reader(reader)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))
+    }
+}
@@ -0,0 +1,65 @@
+//! Helpers shared by the unit tests
+
+use std::fmt::Debug;
+
+use rust_decimal::Decimal;
+use trading_types::common::{Fill, Order, OrderRecord, RequestId, SelectionId, Side, Size, Tick};
+
+/// Builds an [`OrderRecord`] out of the parts that got matched and the part that is left
+#[derive(Debug)]
+pub struct OrderBuilder(OrderRecord);
+
+pub fn back(selection: u32, tick: Decimal) -> OrderBuilder {
+    OrderBuilder::new(Side::Back, selection, tick)
+}
+
+pub fn lay(selection: u32, tick: Decimal) -> OrderBuilder {
+    OrderBuilder::new(Side::Lay, selection, tick)
+}
+
+impl OrderBuilder {
+    fn new(side: Side, selection: u32, tick: Decimal) -> Self {
+        let order = Order {
+            selection: SelectionId(selection),
+            tick: Tick(tick),
+            size: Size(Decimal::ZERO),
+            side,
+        };
+        Self(OrderRecord::new(RequestId("request".to_string()), &order))
+    }
+
+    /// Match `size` more of the order at `price`
+    pub fn matched_at(mut self, price: Decimal, size: Decimal) -> Self {
+        self.0.original_size.0 += size;
+        self.0.remaining_size.0 += size;
+        self.0.add_fill(Fill { price: Tick(price), size: Size(size) });
+        self
+    }
+
+    /// Match `size` more of the order at its own tick
+    pub fn matched(self, size: Decimal) -> Self {
+        let tick = self.0.tick.0;
+        self.matched_at(tick, size)
+    }
+
+    /// Leave `size` more of the order unmatched
+    pub fn unmatched(mut self, size: Decimal) -> Self {
+        self.0.original_size.0 += size;
+        self.0.remaining_size.0 += size;
+        self
+    }
+
+    pub fn record(&self) -> &OrderRecord {
+        &self.0
+    }
+}
+
+/// Check `run` against a table of inputs and the output each of them should give
+pub fn check_cases<I: Debug, O: PartialEq + Debug>(
+    cases: impl IntoIterator<Item = (I, O)>,
+    run: impl Fn(&I) -> O,
+) {
+    for (input, expected) in cases {
+        assert_eq!(run(&input), expected, "{input:?}");
+    }
+}
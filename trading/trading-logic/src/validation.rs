@@ -0,0 +1,95 @@
+use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
+use trading_types::common::{Order, Selection, Size};
+use trading_types::from_server::RejectReason;
+
+/// Limits that every order has to satisfy before it reaches the order book
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderRules {
+    pub min_stake: Size,
+    pub max_stake: Size,
+    /// Maximum number of decimal places a stake may have
+    pub stake_precision: u32,
+}
+
+impl Default for OrderRules {
+    fn default() -> Self {
+        Self { min_stake: Size(dec!(1)), max_stake: Size(dec!(10000)), stake_precision: 2 }
+    }
+}
+
+impl OrderRules {
+    pub fn validate(
+        &self,
+        order: &Order,
+        market_open: bool,
+        selections: &[Selection],
+    ) -> Result<(), RejectReason> {
+        if !market_open {
+            return Err(RejectReason::MarketNotOpen)
+        }
+        if !selections.iter().any(|selection| selection.id == order.selection) {
+            return Err(RejectReason::UnknownSelection)
+        }
+        if !order.tick.is_valid() {
+            return Err(RejectReason::UnknownTick)
+        }
+        if order.size < self.min_stake {
+            return Err(RejectReason::StakeTooSmall { min: self.min_stake })
+        }
+        if order.size > self.max_stake {
+            return Err(RejectReason::StakeTooLarge { max: self.max_stake })
+        }
+        if order.size.0.normalize().scale() > self.stake_precision {
+            return Err(RejectReason::InvalidStakePrecision { decimals: self.stake_precision })
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use trading_types::common::{SelectionId, Side, Tick};
+
+    use super::*;
+    use crate::testing::check_cases;
+
+    #[test]
+    fn orders_are_checked_against_the_rules() {
+        let order = |selection: u32, tick, size| Order {
+            selection: SelectionId(selection),
+            tick: Tick(tick),
+            size: Size(size),
+            side: Side::Back,
+        };
+        let cases = [
+            ((order(1, dec!(2), dec!(10)), true), Ok(())),
+            ((order(1, dec!(2), dec!(10)), false), Err(RejectReason::MarketNotOpen)),
+            ((order(3, dec!(2), dec!(10)), true), Err(RejectReason::UnknownSelection)),
+            ((order(1, dec!(2.01), dec!(10)), true), Err(RejectReason::UnknownTick)),
+            ((order(1, dec!(2), dec!(1)), true), Ok(())),
+            (
+                (order(1, dec!(2), dec!(0.99)), true),
+                Err(RejectReason::StakeTooSmall { min: Size(dec!(1)) }),
+            ),
+            ((order(1, dec!(2), dec!(10000)), true), Ok(())),
+            (
+                (order(1, dec!(2), dec!(10000.01)), true),
+                Err(RejectReason::StakeTooLarge { max: Size(dec!(10000)) }),
+            ),
+            // Trailing zeros don't count as decimal places
+            ((order(1, dec!(2), dec!(10.500)), true), Ok(())),
+            (
+                (order(1, dec!(2), dec!(10.005)), true),
+                Err(RejectReason::InvalidStakePrecision { decimals: 2 }),
+            ),
+        ];
+        let selections = [
+            Selection { id: SelectionId(1), name: "Home".to_string() },
+            Selection { id: SelectionId(2), name: "Away".to_string() },
+        ];
+        check_cases(cases, |(order, market_open)| {
+            OrderRules::default().validate(order, *market_open, &selections)
+        });
+    }
+}
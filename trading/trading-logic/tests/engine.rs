@@ -0,0 +1,110 @@
+//! Scenarios run against the matching engine
+
+use chrono::{TimeZone, Utc};
+use rust_decimal_macros::dec;
+use trading_logic::engine::{Command, Event, MarketConfig, MatchingEngine, Outcome};
+use trading_types::common::{
+    MarketStatus, Order, RequestId, SelectionId, Side, Size, Tick, TraderId,
+};
+
+fn execute(engine: &mut MatchingEngine, command: Command) -> (Outcome, Vec<Event>) {
+    let now = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+    engine.execute(command, now)
+}
+
+fn trader(name: &str) -> TraderId {
+    TraderId(name.to_string())
+}
+
+fn open_market(engine: &mut MatchingEngine, traders: &[&str]) {
+    for name in traders {
+        execute(engine, Command::RegisterTrader { trader: trader(name) });
+    }
+    execute(engine, Command::SetStatus(MarketStatus::Open)).0.into_transition().unwrap();
+}
+
+fn place(engine: &mut MatchingEngine, name: &str, side: Side, tick: Tick, size: Size) {
+    let order = Order { selection: SelectionId(1), tick, size, side };
+    let request_id = RequestId(format!("{name}-order"));
+    let command = Command::PlaceOrder { trader: trader(name), request_id, order };
+    execute(engine, command).0.into_order().unwrap();
+}
+
+#[test]
+fn cash_out_is_covered_by_the_position_it_closes() {
+    let mut engine = MatchingEngine::new(MarketConfig {
+        round_duration: None,
+        starting_balance: Size(dec!(1000)),
+        ..MarketConfig::default()
+    });
+    open_market(&mut engine, &["backer", "layer", "first", "second"]);
+
+    // The backer puts their whole balance at risk
+    place(&mut engine, "layer", Side::Lay, Tick(dec!(2)), Size(dec!(1000)));
+    place(&mut engine, "backer", Side::Back, Tick(dec!(2)), Size(dec!(1000)));
+    // and the price shortens
+    place(&mut engine, "first", Side::Back, Tick(dec!(1.5)), Size(dec!(1000)));
+    place(&mut engine, "second", Side::Back, Tick(dec!(1.5)), Size(dec!(500)));
+
+    let backer = trader("backer");
+    let command = Command::CashOut {
+        trader: backer.clone(),
+        request_id: RequestId("cash-out".to_string()),
+        selection: SelectionId(1),
+    };
+    let placed = execute(&mut engine, command).0.into_order().unwrap();
+    assert_eq!(placed.remaining, Size(dec!(0)));
+    assert_eq!(
+        placed.fills.iter().map(|fill| fill.matched.0).sum::<rust_decimal::Decimal>(),
+        dec!(1333.33)
+    );
+
+    let positions = engine.positions(&backer);
+    assert_eq!(positions.len(), 1);
+    assert_eq!(positions[0].profit_if_wins, dec!(333.335));
+    assert_eq!(positions[0].profit_if_loses, dec!(333.33));
+    let (_orders, info) = engine.trader_state(&backer).unwrap();
+    assert_eq!(info.exposure, Size(dec!(0)));
+}
+
+#[test]
+fn departed_traders_are_dropped_once_nothing_is_left_in_the_market() {
+    let mut engine = MatchingEngine::default();
+    open_market(&mut engine, &["idle", "resting", "matched", "returning", "counterparty"]);
+    place(&mut engine, "resting", Side::Back, Tick(dec!(3)), Size(dec!(10)));
+    place(&mut engine, "returning", Side::Back, Tick(dec!(3)), Size(dec!(10)));
+    place(&mut engine, "counterparty", Side::Lay, Tick(dec!(2)), Size(dec!(10)));
+    place(&mut engine, "matched", Side::Back, Tick(dec!(2)), Size(dec!(10)));
+
+    let removed = |engine: &mut MatchingEngine, command: Command| {
+        let (_outcome, events) = execute(engine, command);
+        events
+            .into_iter()
+            .filter_map(|event| match event {
+                Event::TraderRemoved { trader } => Some(trader.0),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+    let deregister = |name: &str| Command::DeregisterTrader { trader: trader(name) };
+
+    assert_eq!(removed(&mut engine, deregister("idle")), ["idle"]);
+    assert!(removed(&mut engine, deregister("resting")).is_empty());
+    assert!(removed(&mut engine, deregister("matched")).is_empty());
+    assert!(removed(&mut engine, deregister("returning")).is_empty());
+    execute(&mut engine, Command::RegisterTrader { trader: trader("returning") });
+
+    let cancel = Command::CancelOrders { trader: trader("resting"), selection: None, tick: None };
+    assert_eq!(removed(&mut engine, cancel), ["resting"]);
+
+    // Whoever won or lost money keeps the account for when they come back
+    execute(&mut engine, Command::SetStatus(MarketStatus::Closed)).0.into_transition().unwrap();
+    assert!(removed(&mut engine, Command::Settle(SelectionId(1))).is_empty());
+    assert!(removed(&mut engine, Command::SetStatus(MarketStatus::Inactive)).is_empty());
+    let traders = engine.traders().map(|(trader, _account)| trader.0.as_str()).collect::<Vec<_>>();
+    assert_eq!(traders, ["counterparty", "matched", "returning"]);
+
+    execute(&mut engine, Command::RegisterTrader { trader: trader("matched") });
+    let (_orders, info) = engine.trader_state(&trader("matched")).unwrap();
+    assert_eq!(info.balance, Size(dec!(1009.50)));
+}
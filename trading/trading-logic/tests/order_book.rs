@@ -0,0 +1,353 @@
+//! Invariants of the order book that have to hold after every command, checked against random
+//! command sequences.
+
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{TimeZone, Utc};
+use proptest::prelude::*;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use trading_logic::engine::{Command, Event, MarketConfig, MatchingEngine};
+use trading_types::common::{
+    MarketStatus, Order, RequestId, SelectionId, SelfTradePrevention, Side, Size, Tick, TraderId,
+};
+
+const TRADERS: usize = 3;
+const SELECTIONS: u32 = 2;
+/// Orders are placed on a narrow part of the ladder so that they cross often
+const TICK_INDICES: std::ops::Range<usize> = 40..52;
+
+#[derive(Debug, Clone)]
+enum Step {
+    Place {
+        trader: usize,
+        selection: u32,
+        tick: usize,
+        cents: i64,
+        side: Side,
+    },
+    /// Cancel one of the orders placed so far
+    Cancel {
+        trader: usize,
+        order: usize,
+    },
+    CancelAll {
+        trader: usize,
+        selection: Option<u32>,
+        tick: Option<usize>,
+    },
+    CashOut {
+        trader: usize,
+        selection: u32,
+    },
+    /// Close, settle and reopen the market with an empty book
+    Reset {
+        winner: u32,
+    },
+}
+
+fn side() -> impl Strategy<Value = Side> {
+    prop_oneof![Just(Side::Back), Just(Side::Lay)]
+}
+
+fn step() -> impl Strategy<Value = Step> {
+    prop_oneof![
+        8 => (0..TRADERS, 1..=SELECTIONS, TICK_INDICES, 100..20_000i64, side()).prop_map(
+            |(trader, selection, tick, cents, side)| Step::Place {
+                trader,
+                selection,
+                tick,
+                cents,
+                side
+            }
+        ),
+        2 => (0..TRADERS, any::<usize>()).prop_map(|(trader, order)| Step::Cancel { trader, order }),
+        1 => (0..TRADERS, proptest::option::of(1..=SELECTIONS), proptest::option::of(TICK_INDICES))
+            .prop_map(|(trader, selection, tick)| Step::CancelAll { trader, selection, tick }),
+        1 => (0..TRADERS, 1..=SELECTIONS)
+            .prop_map(|(trader, selection)| Step::CashOut { trader, selection }),
+        1 => (1..=SELECTIONS).prop_map(|winner| Step::Reset { winner }),
+    ]
+}
+
+fn self_trade_prevention() -> impl Strategy<Value = Option<SelfTradePrevention>> {
+    prop_oneof![
+        Just(None),
+        Just(Some(SelfTradePrevention::CancelResting)),
+        Just(Some(SelfTradePrevention::CancelIncoming)),
+        Just(Some(SelfTradePrevention::DecrementBoth)),
+    ]
+}
+
+fn trader_id(trader: usize) -> TraderId {
+    TraderId(format!("trader-{trader}"))
+}
+
+/// What the events say happened to a single order
+#[derive(Debug, Default)]
+struct OrderHistory {
+    placed: Decimal,
+    matched: Decimal,
+    cancelled: Decimal,
+}
+
+/// Runs the steps on an engine and keeps track of the events it emitted
+struct Harness {
+    engine: MatchingEngine,
+    histories: HashMap<(TraderId, RequestId), OrderHistory>,
+    request_ids: Vec<(TraderId, RequestId)>,
+    next_request: usize,
+}
+
+impl Harness {
+    fn new(self_trade_prevention: Option<SelfTradePrevention>) -> Self {
+        let config = MarketConfig {
+            selections: (1..=SELECTIONS)
+                .map(|id| trading_types::common::Selection {
+                    id: SelectionId(id),
+                    name: format!("Selection {id}"),
+                })
+                .collect(),
+            round_duration: None,
+            starting_balance: Size(dec!(1000000)),
+            self_trade_prevention,
+            ..MarketConfig::default()
+        };
+        let mut harness = Self {
+            engine: MatchingEngine::new(config),
+            histories: HashMap::new(),
+            request_ids: vec![],
+            next_request: 0,
+        };
+        for trader in 0..TRADERS {
+            harness.execute(Command::RegisterTrader { trader: trader_id(trader) });
+        }
+        harness.execute(Command::SetStatus(MarketStatus::Open));
+        harness
+    }
+
+    fn execute(&mut self, command: Command) {
+        let now = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let (_outcome, events) = self.engine.execute(command, now);
+        for event in events {
+            self.record(event);
+        }
+    }
+
+    fn record(&mut self, event: Event) {
+        match event {
+            Event::OrderAccepted { trader, request_id, order } => {
+                self.histories.entry((trader, request_id)).or_default().placed += order.size.0;
+            }
+            Event::OrderFilled { trader, request_id, matched, .. } => {
+                self.histories.entry((trader, request_id)).or_default().matched += matched.0;
+            }
+            Event::OrderCancelled { trader, request_id, size } => {
+                self.histories.entry((trader, request_id)).or_default().cancelled += size.0;
+            }
+            Event::SelfTradePrevented { trader, prevented } => {
+                self.histories
+                    .entry((trader.clone(), prevented.incoming))
+                    .or_default()
+                    .cancelled += prevented.incoming_cancelled.0;
+                self.histories.entry((trader, prevented.resting)).or_default().cancelled +=
+                    prevented.resting_cancelled.0;
+            }
+            Event::BookReset => self.histories.clear(),
+            _ => {}
+        }
+    }
+
+    fn new_request_id(&mut self, trader: &TraderId) -> RequestId {
+        self.next_request += 1;
+        let request_id = RequestId(format!("request-{}", self.next_request));
+        self.request_ids.push((trader.clone(), request_id.clone()));
+        request_id
+    }
+
+    fn apply(&mut self, step: Step) {
+        match step {
+            Step::Place { trader, selection, tick, cents, side } => {
+                let trader = trader_id(trader);
+                let request_id = self.new_request_id(&trader);
+                let order = Order {
+                    selection: SelectionId(selection),
+                    tick: Tick::from_index(tick).unwrap(),
+                    size: Size(Decimal::new(cents, 2)),
+                    side,
+                };
+                self.execute(Command::PlaceOrder { trader, request_id, order });
+            }
+            Step::Cancel { trader, order } => {
+                if self.request_ids.is_empty() {
+                    return
+                }
+                // Mostly cancel own orders, but also try cancelling someone else's
+                let (owner, request_id) = self.request_ids[order % self.request_ids.len()].clone();
+                let trader = if order % 4 == 0 { trader_id(trader) } else { owner };
+                self.execute(Command::CancelOrder { trader, request_id });
+            }
+            Step::CancelAll { trader, selection, tick } => {
+                self.execute(Command::CancelOrders {
+                    trader: trader_id(trader),
+                    selection: selection.map(SelectionId),
+                    tick: tick.and_then(Tick::from_index),
+                });
+            }
+            Step::CashOut { trader, selection } => {
+                let trader = trader_id(trader);
+                let request_id = self.new_request_id(&trader);
+                self.execute(Command::CashOut {
+                    trader,
+                    request_id,
+                    selection: SelectionId(selection),
+                });
+            }
+            Step::Reset { winner } => {
+                self.execute(Command::SetStatus(MarketStatus::Closed));
+                self.execute(Command::Settle(SelectionId(winner)));
+                self.execute(Command::SetStatus(MarketStatus::Inactive));
+                self.execute(Command::SetStatus(MarketStatus::Open));
+            }
+        }
+    }
+
+    fn check_invariants(&self) -> Result<(), TestCaseError> {
+        self.check_size_conservation()?;
+        self.check_book_not_crossed()?;
+        self.check_matched_volume()?;
+        self.check_trader_views()
+    }
+
+    /// Every placed stake is either still open, matched or cancelled
+    fn check_size_conservation(&self) -> Result<(), TestCaseError> {
+        for ((trader, request_id), history) in self.histories.iter() {
+            let record =
+                self.engine.trader(trader).and_then(|account| account.orders.get(request_id));
+            let (open, matched) = record.map_or((Decimal::ZERO, Decimal::ZERO), |record| {
+                (record.remaining_size.0, record.matched_size.0)
+            });
+            prop_assert_eq!(
+                history.placed,
+                open + history.matched + history.cancelled,
+                "{:?} {:?}: {:?}, open {}",
+                trader,
+                request_id,
+                history,
+                open
+            );
+            prop_assert_eq!(matched, history.matched, "{:?} {:?}", trader, request_id);
+            prop_assert!(open >= Decimal::ZERO, "{:?} {:?} has negative size", trader, request_id);
+        }
+        Ok(())
+    }
+
+    /// A resting back would match every lay at its tick or above, so all backs have to rest above
+    /// all lays
+    fn check_book_not_crossed(&self) -> Result<(), TestCaseError> {
+        let snapshot = self.engine.snapshot();
+        for selection in 1..=SELECTIONS {
+            let ticks =
+                snapshot.ticks.iter().filter(|tick| tick.selection == SelectionId(selection));
+            let lowest_back = ticks
+                .clone()
+                .filter(|tick| tick.available_backs.0 > Decimal::ZERO)
+                .map(|tick| tick.tick)
+                .min();
+            let highest_lay = ticks
+                .filter(|tick| tick.available_lays.0 > Decimal::ZERO)
+                .map(|tick| tick.tick)
+                .max();
+            if let (Some(back), Some(lay)) = (lowest_back, highest_lay) {
+                prop_assert!(
+                    back > lay,
+                    "Selection {} is crossed: back at {:?}, lay at {:?}",
+                    selection,
+                    back,
+                    lay
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Every match has a back and a lay side of the same size at the same price
+    fn check_matched_volume(&self) -> Result<(), TestCaseError> {
+        let mut matched = BTreeMap::<(SelectionId, Tick), (Decimal, Decimal)>::new();
+        for (_trader, account) in self.engine.traders() {
+            for order in account.orders.values() {
+                for fill in order.fills.iter() {
+                    let (backs, lays) = matched.entry((order.selection, fill.price)).or_default();
+                    match order.side {
+                        Side::Back => *backs += fill.size.0,
+                        Side::Lay => *lays += fill.size.0,
+                    }
+                }
+            }
+        }
+        for ((selection, tick), (backs, lays)) in matched.iter() {
+            prop_assert_eq!(backs, lays, "Unbalanced matches on {:?} at {:?}", selection, tick);
+        }
+
+        // Wash trades are left out of the ladder totals, so they only add up without them
+        if self.engine.config().self_trade_prevention.is_some() {
+            for tick_data in self.engine.snapshot().ticks {
+                let (backs, _lays) = matched
+                    .get(&(tick_data.selection, tick_data.tick))
+                    .copied()
+                    .unwrap_or_default();
+                prop_assert_eq!(
+                    tick_data.total_matched.0,
+                    backs,
+                    "Ladder total differs from the fills on {:?} at {:?}",
+                    tick_data.selection,
+                    tick_data.tick
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// The open stakes of the traders' orders add up to what the ladder shows
+    fn check_trader_views(&self) -> Result<(), TestCaseError> {
+        let mut open = BTreeMap::<(SelectionId, Tick), (Decimal, Decimal)>::new();
+        for (_trader, account) in self.engine.traders() {
+            for order in account.orders.values() {
+                let (backs, lays) = open.entry((order.selection, order.tick)).or_default();
+                match order.side {
+                    Side::Back => *backs += order.remaining_size.0,
+                    Side::Lay => *lays += order.remaining_size.0,
+                }
+            }
+        }
+        for tick_data in self.engine.snapshot().ticks {
+            let (backs, lays) =
+                open.get(&(tick_data.selection, tick_data.tick)).copied().unwrap_or_default();
+            prop_assert_eq!(
+                (tick_data.available_backs.0, tick_data.available_lays.0),
+                (backs, lays),
+                "Ladder differs from the trader orders on {:?} at {:?}",
+                tick_data.selection,
+                tick_data.tick
+            );
+        }
+        Ok(())
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(256))]
+
+    #[test]
+    fn order_book_invariants_hold(
+        self_trade_prevention in self_trade_prevention(),
+        steps in proptest::collection::vec(step(), 1..80),
+    ) {
+        let mut harness = Harness::new(self_trade_prevention);
+        harness.check_invariants()?;
+        for step in steps {
+            harness.apply(step);
+            harness.check_invariants()?;
+        }
+    }
+}
@@ -1,3 +1,7 @@
+use std::sync::OnceLock;
+
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
@@ -9,6 +13,16 @@ pub struct TraderId(pub String);
 #[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
 pub struct RequestId(pub String);
 
+/// A runner of a market that can be traded on its own ladder
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub struct SelectionId(pub u32);
+
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub struct Selection {
+    pub id: SelectionId,
+    pub name: String,
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
 pub struct Tick(pub rust_decimal::Decimal);
 
@@ -17,17 +31,140 @@ pub struct Size(pub rust_decimal::Decimal);
 
 #[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
 pub struct Order {
+    pub selection: SelectionId,
     pub tick: Tick,
     pub size: Size,
     pub side: Side,
 }
 
+/// The state of a single order placed by a trader
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub struct OrderRecord {
+    pub request_id: RequestId,
+    pub selection: SelectionId,
+    pub tick: Tick,
+    pub side: Side,
+    pub original_size: Size,
+    pub remaining_size: Size,
+    pub matched_size: Size,
+    pub fills: Vec<Fill>,
+}
+
+/// A part of an order that got matched at a given price
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub struct Fill {
+    pub price: Tick,
+    pub size: Size,
+}
+
+impl OrderRecord {
+    pub fn new(request_id: RequestId, order: &Order) -> Self {
+        Self {
+            request_id,
+            selection: order.selection,
+            tick: order.tick,
+            side: order.side,
+            original_size: order.size,
+            remaining_size: order.size,
+            matched_size: Size(rust_decimal::Decimal::ZERO),
+            fills: Vec::new(),
+        }
+    }
+
+    pub fn add_fill(&mut self, fill: Fill) {
+        self.remaining_size.0 -= fill.size.0;
+        self.matched_size.0 += fill.size.0;
+        self.fills.push(fill);
+    }
+
+    /// Stake weighted average of the prices this order got matched at
+    pub fn average_matched_price(&self) -> Option<rust_decimal::Decimal> {
+        if self.matched_size.0.is_zero() {
+            return None
+        }
+        let weighted =
+            self.fills.iter().map(|fill| fill.price.0 * fill.size.0).sum::<rust_decimal::Decimal>();
+        Some(weighted / self.matched_size.0)
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
 pub enum Side {
     Back,
     Lay,
 }
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub enum MarketStatus {
+    Inactive,
+    Open,
+    Suspended,
+    InPlay,
+    Closed,
+    Settled,
+}
+
+impl MarketStatus {
+    /// Whether orders can be placed while the market is in this status
+    pub fn is_tradable(&self) -> bool {
+        matches!(self, MarketStatus::Open | MarketStatus::InPlay)
+    }
+
+    pub fn can_transition_to(&self, next: MarketStatus) -> bool {
+        use MarketStatus::*;
+
+        matches!(
+            (self, next),
+            (Inactive, Open) |
+                (Open, Suspended | InPlay | Closed) |
+                (Suspended, Open | InPlay | Closed) |
+                (InPlay, Suspended | Closed) |
+                (Closed, Settled | Inactive) |
+                (Settled, Inactive)
+        )
+    }
+}
+
+impl std::fmt::Display for MarketStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MarketStatus::Inactive => write!(f, "Inactive"),
+            MarketStatus::Open => write!(f, "Open"),
+            MarketStatus::Suspended => write!(f, "Suspended"),
+            MarketStatus::InPlay => write!(f, "In-play"),
+            MarketStatus::Closed => write!(f, "Closed"),
+            MarketStatus::Settled => write!(f, "Settled"),
+        }
+    }
+}
+
+/// How a single selection of a market ended
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub enum Outcome {
+    Won,
+    Lost,
+}
+
+impl std::fmt::Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Outcome::Won => write!(f, "Won"),
+            Outcome::Lost => write!(f, "Lost"),
+        }
+    }
+}
+
+/// What happens when an incoming order would match a resting order of the same trader
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub enum SelfTradePrevention {
+    /// Cancel the resting order and keep matching the incoming one
+    CancelResting,
+    /// Cancel whatever is left of the incoming order
+    CancelIncoming,
+    /// Reduce both orders by the size they would have matched
+    DecrementBoth,
+}
+
 impl std::fmt::Display for Side {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -37,16 +174,69 @@ impl std::fmt::Display for Side {
     }
 }
 
-impl Tick {
-    pub fn all() -> Vec<Tick> {
-        // Generate ticks from 1.01 to 2.0 with a step 0.01
+/// Price increments of the odds ladder as `(upper bound, increment)` pairs. The increment applies
+/// to every tick below the upper bound.
+const LADDER_INCREMENTS: [(Decimal, Decimal); 10] = [
+    (dec!(2), dec!(0.01)),
+    (dec!(3), dec!(0.02)),
+    (dec!(4), dec!(0.05)),
+    (dec!(6), dec!(0.1)),
+    (dec!(10), dec!(0.2)),
+    (dec!(20), dec!(0.5)),
+    (dec!(30), dec!(1)),
+    (dec!(50), dec!(2)),
+    (dec!(100), dec!(5)),
+    (dec!(1000), dec!(10)),
+];
+
+fn ladder() -> &'static [Tick] {
+    static LADDER: OnceLock<Vec<Tick>> = OnceLock::new();
+    LADDER.get_or_init(|| {
         let mut ticks = Vec::new();
-        let mut tick = rust_decimal::Decimal::new(145, 2);
-        while tick < rust_decimal::Decimal::new(155, 2) {
-            ticks.push(Tick(tick));
-            tick += rust_decimal::Decimal::new(1, 2);
+        let mut tick = Tick::MIN.0;
+        for (upper_bound, increment) in LADDER_INCREMENTS {
+            while tick < upper_bound {
+                ticks.push(Tick(tick));
+                tick += increment;
+            }
         }
+        ticks.push(Tick::MAX);
         ticks
+    })
+}
+
+impl Tick {
+    pub const MIN: Tick = Tick(dec!(1.01));
+    pub const MAX: Tick = Tick(dec!(1000));
+
+    /// Every tick of the odds ladder, from 1.01 to 1000
+    pub fn all() -> Vec<Tick> {
+        ladder().to_vec()
+    }
+
+    pub fn from_index(index: usize) -> Option<Tick> {
+        ladder().get(index).copied()
+    }
+
+    /// Position of the tick on the ladder, `None` if it's not a valid tick
+    pub fn index(&self) -> Option<usize> {
+        ladder().binary_search(self).ok()
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.index().is_some()
+    }
+
+    /// The closest tick on the ladder that is higher than this one
+    pub fn next(&self) -> Option<Tick> {
+        let idx = ladder().partition_point(|tick| tick <= self);
+        Tick::from_index(idx)
+    }
+
+    /// The closest tick on the ladder that is lower than this one
+    pub fn prev(&self) -> Option<Tick> {
+        let idx = ladder().partition_point(|tick| tick < self);
+        idx.checked_sub(1).and_then(Tick::from_index)
     }
 }
 
@@ -57,3 +247,60 @@ impl std::ops::Add<&Size> for Size {
         Size(self.0 + rhs.0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ladder_runs_from_min_to_max() {
+        let ticks = Tick::all();
+        assert_eq!(ticks.len(), 350);
+        assert_eq!(ticks.first(), Some(&Tick::MIN));
+        assert_eq!(ticks.last(), Some(&Tick::MAX));
+        assert!(ticks.windows(2).all(|pair| pair[0] < pair[1]));
+        for (index, tick) in ticks.iter().enumerate() {
+            assert_eq!(tick.index(), Some(index));
+            assert_eq!(Tick::from_index(index), Some(*tick));
+        }
+        assert_eq!(Tick::from_index(ticks.len()), None);
+    }
+
+    #[test]
+    fn next_and_prev_cross_the_increment_bands() {
+        let steps = [
+            (dec!(1.01), Some(dec!(1.02))),
+            (dec!(1.99), Some(dec!(2.00))),
+            (dec!(2.00), Some(dec!(2.02))),
+            (dec!(3.95), Some(dec!(4.0))),
+            (dec!(990), Some(dec!(1000))),
+            (dec!(1000), None),
+        ];
+        for (tick, next) in steps {
+            assert_eq!(Tick(tick).next(), next.map(Tick), "next of {tick}");
+            if let Some(next) = next {
+                assert_eq!(Tick(next).prev(), Some(Tick(tick)), "prev of {next}");
+            }
+        }
+        assert_eq!(Tick::MIN.prev(), None);
+        assert_eq!(Tick::MAX.next(), None);
+        // Off the ladder, the closest ticks on either side
+        assert_eq!(Tick(dec!(2.01)).next(), Some(Tick(dec!(2.02))));
+        assert_eq!(Tick(dec!(2.01)).prev(), Some(Tick(dec!(2))));
+    }
+
+    #[test]
+    fn valid_ticks_ignore_the_scale() {
+        let ticks = [
+            (dec!(2), true),
+            (dec!(2.00), true),
+            (dec!(4.0), true),
+            (dec!(2.01), false),
+            (dec!(1.00), false),
+            (dec!(1000.5), false),
+        ];
+        for (tick, valid) in ticks {
+            assert_eq!(Tick(tick).is_valid(), valid, "{tick}");
+        }
+    }
+}
@@ -1,23 +1,126 @@
-use std::collections::HashMap;
-
 use serde::{Deserialize, Serialize};
 
-use crate::common::{Order, Size, Tick};
+use crate::common::{
+    MarketStatus, OrderRecord, RequestId, Selection, SelectionId, SelfTradePrevention, Side, Size,
+    Tick,
+};
+
+/// Websocket close code for a connection with a trader id another connection is already using
+pub const TRADER_ALREADY_CONNECTED: u16 = 4001;
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum ServerMessage {
     TraderTimeAck,
     ConnectionInfo(Latency),
-    TickSetWhole(Vec<TickData>),
-    TickUpdate(TickData),
-    NewLatestMatch(TickData),
+    Selections(Vec<Selection>),
+    TickSetWhole(LadderSnapshot),
+    LadderDelta(LadderDelta),
+    Trade(Trade),
+    TradeHistory(Vec<Trade>),
+    MarketStatus(MarketStatus),
     OrderStateUpdate(TraderOrders),
+    OrderAccepted(RequestId),
+    OrderRejected { request_id: RequestId, reason: RejectReason },
+    OrderFilled { request_id: RequestId, price: Tick, matched: Size, remaining: Size },
+    SelfTradePrevented(SelfTradePrevented),
+    Settlement(Settlement),
+    TraderInfo(TraderInfo),
+    Positions(Vec<Position>),
 }
 
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+/// An incoming order that would have matched a resting order of the same trader
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct SelfTradePrevented {
+    pub mode: SelfTradePrevention,
+    pub incoming: RequestId,
+    pub resting: RequestId,
+    pub incoming_cancelled: Size,
+    pub resting_cancelled: Size,
+}
+
+/// The result of a settled market for a single trader
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct Settlement {
+    pub winner: SelectionId,
+    /// Profit or loss of the matched orders before commission
+    pub profit_and_loss: Size,
+    pub commission: Size,
+    pub balance: Size,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum RejectReason {
+    UnknownTrader,
+    UnknownSelection,
+    UnknownTick,
+    DuplicateRequestId,
+    MarketUnavailable,
+    MarketNotOpen,
+    StakeTooSmall { min: Size },
+    StakeTooLarge { max: Size },
+    InvalidStakePrecision { decimals: u32 },
+    InsufficientFunds { available: Size },
+    NothingToCashOut,
+}
+
+impl std::fmt::Display for RejectReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RejectReason::UnknownTrader => write!(f, "Trader is not registered"),
+            RejectReason::UnknownSelection => write!(f, "Selection is not part of the market"),
+            RejectReason::UnknownTick => write!(f, "Tick is not on the ladder"),
+            RejectReason::DuplicateRequestId => write!(f, "Request ID has already been used"),
+            RejectReason::MarketUnavailable => write!(f, "Market is unavailable"),
+            RejectReason::MarketNotOpen => write!(f, "Market is not open"),
+            RejectReason::StakeTooSmall { min } => write!(f, "Stake must be at least {}", min.0),
+            RejectReason::StakeTooLarge { max } => write!(f, "Stake must be at most {}", max.0),
+            RejectReason::InvalidStakePrecision { decimals } => {
+                write!(f, "Stake can have at most {decimals} decimal places")
+            }
+            RejectReason::InsufficientFunds { available } => {
+                write!(f, "Insufficient funds, {} available", available.0.round_dp(2))
+            }
+            RejectReason::NothingToCashOut => write!(f, "There is no position to cash out"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub struct TraderOrders {
-    pub unmatched_orders: HashMap<Tick, Order>,
-    pub matched_orders: HashMap<Tick, Order>,
+    pub orders: Vec<OrderRecord>,
+}
+
+impl TraderOrders {
+    /// Orders that still have some size resting in the order book
+    pub fn unmatched(&self) -> impl Iterator<Item = &OrderRecord> {
+        self.orders.iter().filter(|order| order.remaining_size.0 > rust_decimal::Decimal::ZERO)
+    }
+
+    /// Orders that have been at least partially matched
+    pub fn matched(&self) -> impl Iterator<Item = &OrderRecord> {
+        self.orders.iter().filter(|order| order.matched_size.0 > rust_decimal::Decimal::ZERO)
+    }
+}
+
+/// The whole ladder of every selection. Ladder updates with a sequence number above `sequence`
+/// apply on top of it.
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct LadderSnapshot {
+    pub sequence: u64,
+    pub ticks: Vec<TickData>,
+    /// The tick every selection last matched at
+    pub latest_matches: Vec<TickData>,
+}
+
+/// The ladder changes between two update sequence numbers, with only the latest state of every
+/// changed tick. Applies on top of a ladder that is at least at `previous`.
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct LadderDelta {
+    pub previous: u64,
+    pub sequence: u64,
+    pub ticks: Vec<TickData>,
+    /// Selections that matched at a new tick
+    pub latest_matches: Vec<TickData>,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
@@ -25,19 +128,112 @@ pub struct TickData {
     pub total_matched: Size,
     pub available_backs: Size,
     pub available_lays: Size,
+    pub selection: SelectionId,
     pub tick: Tick,
 }
 
+/// A single match between an incoming order and a resting one
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct Trade {
+    pub selection: SelectionId,
+    pub price: Tick,
+    pub size: Size,
+    /// Side of the incoming order that caused the match
+    pub aggressor_side: Side,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    /// Increases by one with every trade of the market
+    pub sequence: u64,
+}
+
+/// Length of the time buckets trades are aggregated into
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub enum CandleInterval {
+    #[serde(rename = "1s")]
+    OneSecond,
+    #[serde(rename = "10s")]
+    TenSeconds,
+    #[serde(rename = "1m")]
+    OneMinute,
+}
+
+impl CandleInterval {
+    pub const ALL: [CandleInterval; 3] =
+        [CandleInterval::OneSecond, CandleInterval::TenSeconds, CandleInterval::OneMinute];
+
+    pub fn seconds(&self) -> i64 {
+        match self {
+            CandleInterval::OneSecond => 1,
+            CandleInterval::TenSeconds => 10,
+            CandleInterval::OneMinute => 60,
+        }
+    }
+
+    /// Start of the bucket the timestamp falls into
+    pub fn bucket_start(&self, timestamp: chrono::DateTime<chrono::Utc>) -> i64 {
+        let seconds = timestamp.timestamp();
+        seconds - seconds.rem_euclid(self.seconds())
+    }
+}
+
+impl std::fmt::Display for CandleInterval {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CandleInterval::OneSecond => write!(f, "1s"),
+            CandleInterval::TenSeconds => write!(f, "10s"),
+            CandleInterval::OneMinute => write!(f, "1m"),
+        }
+    }
+}
+
+/// Trades of a single selection aggregated over one interval
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct Candle {
+    pub selection: SelectionId,
+    pub interval: CandleInterval,
+    /// Unix timestamp in seconds of the start of the interval
+    pub start: i64,
+    pub open: Tick,
+    pub high: Tick,
+    pub low: Tick,
+    pub close: Tick,
+    pub volume: Size,
+    /// Volume weighted average price
+    pub vwap: rust_decimal::Decimal,
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
 pub struct Latency {
     pub ms: u64,
 }
 
+/// Matched orders of a trader on a single selection
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct Position {
+    pub selection: SelectionId,
+    pub back_stake: Size,
+    pub lay_liability: Size,
+    pub average_back_price: Option<rust_decimal::Decimal>,
+    pub average_lay_price: Option<rust_decimal::Decimal>,
+    pub profit_if_wins: rust_decimal::Decimal,
+    pub profit_if_loses: rust_decimal::Decimal,
+    /// The order that would equalise the profit of both outcomes at the current best price
+    pub cash_out: Option<Hedge>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct Hedge {
+    pub side: Side,
+    pub price: Tick,
+    pub size: Size,
+    /// Profit of the position whichever way the selection ends once the hedge is matched, the
+    /// worse of the two when the hedge is capped at the maximum stake
+    pub profit: rust_decimal::Decimal,
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
 pub struct TraderInfo {
     pub balance: Size,
     pub exposure: Size,
-    pub orders: Vec<Order>,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
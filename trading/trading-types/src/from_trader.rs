@@ -1,11 +1,22 @@
 use serde::{Deserialize, Serialize};
 
-use crate::common::{Order, RequestId};
+use crate::common::{Order, RequestId, SelectionId, Tick};
 
 #[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
 pub enum TraderMessage {
     PlaceOrder(RequestId, Order),
+    CancelOrder(RequestId),
+    CancelAllAtTick(SelectionId, Tick),
+    CancelAll,
+    /// Place the order that equalises the profit of the position on the selection
+    CashOut(SelectionId),
+    /// Ask for the whole market state again after missing a ladder update or a trade
+    RequestSnapshot,
     // Persist connectivity
-    TraderTime { ms: u64 },
-    TraderTimeAck { ms: u64 },
+    TraderTime {
+        ms: u64,
+    },
+    TraderTimeAck {
+        ms: u64,
+    },
 }